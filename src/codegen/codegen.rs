@@ -0,0 +1,1460 @@
+use parser::parser::{Program, Item, Function, Lambda, Statement, Expr, InterpolationPart, Type, BinaryOp, UnaryOp};
+use parser::lexer::NumberType;
+use parser::span::{Span, Spanned};
+use codegen::error::CodegenError;
+
+use vm::function;
+use vm::context::Context;
+use vm::module::Module;
+use vm::value::Value;
+use vm::builder::{Builder, BasicBlock, IntPredicate, RealPredicate};
+use vm::llvm_type::{self, VMRepresentation};
+use vm::{LLVMRef, LLVMTypeRef};
+
+use std::collections::HashMap;
+use std::mem;
+
+pub type GenResult<T> = Result<T, Spanned<CodegenError>>;
+
+// the C functions strings are made with, which programs can't define for themselves
+const RUNTIME: &[&str] = &["malloc", "snprintf"];
+
+// the types a function was declared with, which its LLVM type can't tell apart, e.g. `u8` from `i8`
+#[derive(Clone)]
+struct Signature {
+    params: Vec<Type>,
+    ret:    Type,
+}
+
+// a variable's stack slot, or the address of an array element
+#[derive(Clone)]
+struct Local {
+    slot: Value,
+    ty:   Type,
+}
+
+// the `main` top-level statements are generated into, picked up again at each of them
+struct Main {
+    block:  BasicBlock,
+    locals: HashMap<String, Local>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Integer,
+    Float,
+    Bool,
+    Other,
+}
+
+fn kind(ty: &Type) -> Kind {
+    match *ty {
+        Type::Int                       => Kind::Integer,
+        Type::Float                     => Kind::Float,
+        Type::Bool                      => Kind::Bool,
+        Type::Number(t) if t.is_float() => Kind::Float,
+        Type::Number(_)                 => Kind::Integer,
+        _                               => Kind::Other,
+    }
+}
+
+// a number written without a suffix, which takes the type of whatever it's used with
+fn is_untyped(expr: &Spanned<Expr>) -> bool {
+    match expr.node {
+        Expr::Integer(_, None) | Expr::Float(_, None) => true,
+        _                                             => false,
+    }
+}
+
+// unsigned numbers divide, compare and shift right differently
+fn is_signed(ty: &Type) -> bool {
+    match *ty {
+        Type::Number(t) => t.is_signed(),
+        _               => true,
+    }
+}
+
+fn comparison(op: BinaryOp, signed: bool) -> Option<(IntPredicate, RealPredicate)> {
+    let int = match (op, signed) {
+        (BinaryOp::Equal, _)            => IntPredicate::Equal,
+        (BinaryOp::NotEqual, _)         => IntPredicate::NotEqual,
+        (BinaryOp::Less, true)          => IntPredicate::SignedLess,
+        (BinaryOp::Less, false)         => IntPredicate::UnsignedLess,
+        (BinaryOp::LessEqual, true)     => IntPredicate::SignedLessEqual,
+        (BinaryOp::LessEqual, false)    => IntPredicate::UnsignedLessEqual,
+        (BinaryOp::Greater, true)       => IntPredicate::SignedGreater,
+        (BinaryOp::Greater, false)      => IntPredicate::UnsignedGreater,
+        (BinaryOp::GreaterEqual, true)  => IntPredicate::SignedGreaterEqual,
+        (BinaryOp::GreaterEqual, false) => IntPredicate::UnsignedGreaterEqual,
+        _                               => return None,
+    };
+
+    // `!=` is also true when either side is NaN, like everywhere else
+    let real = match op {
+        BinaryOp::Equal     => RealPredicate::OrderedEqual,
+        BinaryOp::NotEqual  => RealPredicate::UnorderedNotEqual,
+        BinaryOp::Less      => RealPredicate::OrderedLess,
+        BinaryOp::LessEqual => RealPredicate::OrderedLessEqual,
+        BinaryOp::Greater   => RealPredicate::OrderedGreater,
+        _                   => RealPredicate::OrderedGreaterEqual,
+    };
+
+    Some((int, real))
+}
+
+// generates one module from any number of programs. every program is declared before any is
+// defined, so functions can be called from anywhere no matter the order they're written in
+pub struct Generator<'a> {
+    context:    &'a Context,
+    module:     Module,
+    builder:    Builder,
+    signatures: HashMap<String, Signature>,
+    main:       Option<Main>,
+
+    // what the function being generated returns
+    ret:        Type,
+    scopes:     Vec<HashMap<String, Local>>,
+    // where `continue` and `break` go in each enclosing loop
+    loops:      Vec<(BasicBlock, BasicBlock)>,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(context: &'a Context, name: &str) -> Generator<'a> {
+        Generator {
+            context:    context,
+            module:     Module::new(name, context),
+            builder:    Builder::new(context),
+            signatures: HashMap::new(),
+            main:       None,
+            ret:        Type::Void,
+            scopes:     Vec::new(),
+            loops:      Vec::new(),
+        }
+    }
+
+    pub fn declare(&mut self, program: &Program) -> Vec<Spanned<CodegenError>> {
+        let mut errors = Vec::new();
+
+        for item in &program.items {
+            if let Item::Function(ref f) = item.node {
+                if let Err(e) = self.declare_function(f, item.span) {
+                    errors.push(e)
+                }
+            }
+        }
+
+        errors
+    }
+
+    pub fn define(&mut self, program: &Program) -> Vec<Spanned<CodegenError>> {
+        let mut errors = Vec::new();
+
+        for item in &program.items {
+            let result = match item.node {
+                Item::Function(ref f)  => self.define_function(f, item.span),
+                Item::Statement(ref s) => self.top_level(s, item.span),
+            };
+
+            if let Err(e) = result {
+                errors.push(e)
+            }
+        }
+
+        errors
+    }
+
+    // ends `main` and hands over the module. anything failing verification here is a bug in the generator
+    pub fn finish(mut self) -> Result<Module, String> {
+        if let Some(main) = self.main.take() {
+            if main.block.terminator().is_none() {
+                self.builder.move_to_end(main.block);
+                self.builder.return_value(0i32.to_representation(self.context));
+            }
+        }
+
+        self.module.verify()?;
+
+        Ok(self.module)
+    }
+
+    fn declare_function(&mut self, f: &Function, span: Span) -> GenResult<()> {
+        if self.module.get_function(&f.name).is_some() {
+            return Err(Spanned::new(CodegenError::Redefined(f.name.clone()), span))
+        }
+
+        if RUNTIME.contains(&f.name.as_str()) {
+            return Err(Spanned::new(CodegenError::Reserved(f.name.clone()), span))
+        }
+
+        // `main` is what `run` calls and the C runtime starts from
+        if f.name == "main" && (!f.params.is_empty() || f.ret != Type::Number(NumberType::I32)) {
+            return Err(Spanned::new(CodegenError::InvalidMain, span))
+        }
+
+        let mut params = Vec::new();
+
+        for param in &f.params {
+            params.push(self.llvm_type(&param.ty, span)?);
+        }
+
+        let ret = self.llvm_type(&f.ret, span)?;
+
+        function::Function::new(&self.module, &f.name, &mut params, ret);
+
+        self.signatures.insert(f.name.clone(), Signature {
+            params: f.params.iter().map(|p| p.ty.clone()).collect(),
+            ret:    f.ret.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn define_function(&mut self, f: &Function, span: Span) -> GenResult<()> {
+        // functions that failed to declare, or were already defined by an earlier definition, are skipped
+        let function = match self.module.get_function(&f.name) {
+            Some(function) if function.basic_blocks().next().is_none() => function,
+            _                                                          => return Ok(()),
+        };
+
+        let entry = function.new_basic_block("entry");
+
+        self.builder.move_to_end(entry);
+
+        self.ret    = f.ret.clone();
+        self.scopes = vec![HashMap::new()];
+        self.loops  = Vec::new();
+
+        // parameters are copied into variables, so they can be assigned to like any other
+        for (i, param) in f.params.iter().enumerate() {
+            let value = function.param(i as u32);
+
+            self.declare_local(&param.name, &param.ty, Some(value), span)?;
+        }
+
+        self.statement(&f.body, span)?;
+
+        if !self.terminated() {
+            if f.ret != Type::Void {
+                return Err(Spanned::new(CodegenError::MissingReturn(f.name.clone()), span))
+            }
+
+            self.builder.return_void();
+        }
+
+        Ok(())
+    }
+
+    fn top_level(&mut self, statement: &Statement, span: Span) -> GenResult<()> {
+        if self.main.is_none() {
+            if self.module.get_function("main").is_some() {
+                return Err(Spanned::new(CodegenError::MainWithStatements, span))
+            }
+
+            let function = function::Function::new(&self.module, "main", &mut [], llvm_type::int32_type(self.context));
+
+            self.main = Some(Main {
+                block:  function.new_basic_block("entry"),
+                locals: HashMap::new(),
+            });
+        }
+
+        let mut main = self.main.take().unwrap();
+
+        self.builder.move_to_end(main.block);
+
+        self.ret    = Type::Number(NumberType::I32);
+        self.scopes = vec![main.locals];
+        self.loops  = Vec::new();
+
+        let result = self.statement(statement, span);
+
+        main.block  = self.builder.insert_block();
+        main.locals = self.scopes.remove(0);
+
+        self.main = Some(main);
+
+        result
+    }
+
+    fn llvm_type(&self, ty: &Type, span: Span) -> GenResult<LLVMTypeRef> {
+        let context = self.context;
+
+        Ok(match *ty {
+            Type::Int   => llvm_type::int64_type(context),
+            Type::Float => llvm_type::double_type(context),
+            Type::Bool  => llvm_type::int1_type(context),
+            Type::Str   => llvm_type::pointer_type(llvm_type::int8_type(context), 0),
+            Type::Void  => llvm_type::void_type(context),
+
+            Type::Number(NumberType::F32) => llvm_type::float_type(context),
+            Type::Number(NumberType::F64) => llvm_type::double_type(context),
+            Type::Number(t)               => llvm_type::int_type(t.bits(), context),
+
+            // LLVM has no `void*`, C's `i8*` stands in for it
+            Type::Pointer(ref t) if **t == Type::Void => llvm_type::pointer_type(llvm_type::int8_type(context), 0),
+            Type::Pointer(ref t)                      => llvm_type::pointer_type(self.llvm_type(t, span)?, 0),
+
+            Type::Array(ref t, Some(len)) => llvm_type::array_type(self.llvm_type(t, span)?, len as u32),
+            Type::Array(ref t, None)      => llvm_type::pointer_type(self.llvm_type(t, span)?, 0),
+
+            Type::Function(ref params, ref ret) => {
+                let mut types = Vec::new();
+
+                for param in params {
+                    types.push(self.llvm_type(param, span)?);
+                }
+
+                llvm_type::closure_type(context, &types, self.llvm_type(ret, span)?)
+            }
+
+            Type::Named(ref name) => return Err(Spanned::new(CodegenError::UnknownType(name.clone()), span)),
+        })
+    }
+
+    // an integer literal, of its suffix's type or else the one expected of it, which it has to fit in
+    fn literal(&self, value: i128, suffix: Option<NumberType>, expected: Option<&Type>, span: Span) -> GenResult<(Value, Type)> {
+        let ty = match (suffix, expected) {
+            (Some(t), _)                                  => Type::Number(t),
+            (None, Some(ty)) if kind(ty) == Kind::Integer => ty.clone(),
+            (None, Some(ty)) if kind(ty) == Kind::Float   => ty.clone(),
+            (None, _)                                     => Type::Int,
+        };
+
+        let (min, max) = match ty {
+            Type::Number(t) if t.is_float()  => (i128::MIN, i128::MAX),
+            Type::Number(t) if t.is_signed() => (-(1 << (t.bits() - 1)), (1 << (t.bits() - 1)) - 1),
+            Type::Number(t)                  => (0, (1 << t.bits()) - 1),
+            Type::Int                        => (i64::MIN as i128, i64::MAX as i128),
+            _                                => (i128::MIN, i128::MAX),
+        };
+
+        if value < min || value > max {
+            return Err(Spanned::new(CodegenError::OutOfRange { value: value.to_string(), ty: ty }, span))
+        }
+
+        Ok((self.integer(value as i64, &ty), ty))
+    }
+
+    fn integer(&self, n: i64, ty: &Type) -> Value {
+        let context = self.context;
+
+        match *ty {
+            Type::Bool                                                    => (n != 0).to_representation(context),
+            Type::Float | Type::Number(NumberType::F64)                   => (n as f64).to_representation(context),
+            Type::Number(NumberType::F32)                                 => (n as f32).to_representation(context),
+            Type::Number(NumberType::I8)  | Type::Number(NumberType::U8)  => (n as u8).to_representation(context),
+            Type::Number(NumberType::I16) | Type::Number(NumberType::U16) => (n as u16).to_representation(context),
+            Type::Number(NumberType::I32) | Type::Number(NumberType::U32) => (n as u32).to_representation(context),
+            _                                                             => n.to_representation(context),
+        }
+    }
+
+    fn float(&self, n: f64, ty: &Type) -> Value {
+        match *ty {
+            Type::Number(NumberType::F32) => (n as f32).to_representation(self.context),
+            _                             => n.to_representation(self.context),
+        }
+    }
+
+    fn new_block(&self, name: &str) -> BasicBlock {
+        self.builder.insert_block().parent().new_basic_block(name)
+    }
+
+    // goes on in `block`, laid out after everything generated so far
+    fn continue_in(&mut self, block: BasicBlock) {
+        let last = self.builder.insert_block().parent().basic_blocks().last().unwrap();
+
+        if last != block {
+            block.move_after(last);
+        }
+
+        self.builder.move_to_end(block);
+    }
+
+    // once the current block ends in a return or branch, nothing more can go in it
+    fn terminated(&self) -> bool {
+        self.builder.insert_block().terminator().is_some()
+    }
+
+    // the block being added to, unless it's already terminated
+    fn open_block(&self) -> Option<BasicBlock> {
+        if self.terminated() {
+            None
+        } else {
+            Some(self.builder.insert_block())
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<Local> {
+        self.scopes.iter().rev().filter_map(|scope| scope.get(name)).next().cloned()
+    }
+
+    fn declare_local(&mut self, name: &str, ty: &Type, value: Option<Value>, span: Span) -> GenResult<Local> {
+        let slot = self.builder.entry_alloca(self.llvm_type(ty, span)?, name);
+
+        if let Some(value) = value {
+            self.builder.store(value, slot);
+        }
+
+        let local = Local {
+            slot: slot,
+            ty:   ty.clone(),
+        };
+
+        self.scopes.last_mut().unwrap().insert(name.to_string(), local.clone());
+
+        Ok(local)
+    }
+
+    // statements outside of blocks have no span of their own, `span` is the closest one around them
+    fn statement(&mut self, statement: &Statement, span: Span) -> GenResult<()> {
+        // nothing after a return, `break` or `continue` can run
+        if self.terminated() {
+            return Ok(())
+        }
+
+        match *statement {
+            Statement::If(ref c, ref body)                        => self.conditional(c, false, body, None, span),
+            Statement::IfElse(ref c, ref body, ref otherwise)     => self.conditional(c, false, body, Some(&**otherwise), span),
+            Statement::Unless(ref c, ref body)                    => self.conditional(c, true, body, None, span),
+            Statement::UnlessElse(ref c, ref body, ref otherwise) => self.conditional(c, true, body, Some(&**otherwise), span),
+
+            Statement::While(ref c, ref body) => self.while_loop(c, false, body, span),
+            Statement::Until(ref c, ref body) => self.while_loop(c, true, body, span),
+
+            Statement::For(ref name, ref iterable, ref body) => self.for_loop(name, iterable, body, span),
+
+            Statement::Break | Statement::Continue => {
+                let (next, exit) = *self.loops.last().expect("the parser only allows these in loops");
+
+                self.builder.br(if let Statement::Break = *statement { exit } else { next });
+
+                Ok(())
+            }
+
+            Statement::Var(ref name, ref ty, ref value) => {
+                let value = match *value {
+                    Some(ref value) => Some(self.typed(value, ty)?),
+                    None            => None,
+                };
+
+                self.declare_local(name, ty, value, span).map(|_| ())
+            }
+
+            Statement::Block(ref statements) => {
+                self.scopes.push(HashMap::new());
+
+                let result = statements.iter().map(|s| self.statement(&s.node, s.span)).collect();
+
+                self.scopes.pop();
+
+                result
+            }
+
+            Statement::Expr(ref e) => self.expr(e, None).map(|_| ()),
+
+            Statement::Return => {
+                if self.ret != Type::Void {
+                    return Err(Spanned::new(CodegenError::Mismatch { expected: self.ret.clone(), found: Type::Void }, span))
+                }
+
+                self.builder.return_void();
+
+                Ok(())
+            }
+
+            Statement::ReturnWithVal(ref e) => {
+                let ret = self.ret.clone();
+
+                // calls whose result is returned straight away are tail calls
+                let (value, found) = match e.node {
+                    Expr::FnCall(ref name, ref args) => self.call(name, args, e.span, true)?,
+                    _                                => self.expr(e, Some(&ret))?,
+                };
+
+                if found != ret {
+                    return Err(Spanned::new(CodegenError::Mismatch { expected: ret, found: found }, e.span))
+                }
+
+                // `return` with a call to another `void` function is still fine in one
+                if ret == Type::Void {
+                    self.builder.return_void();
+                } else {
+                    self.builder.return_value(value);
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    // `if` and `unless`, the branches join up again after unless they both return or leave a loop
+    fn conditional(&mut self, condition: &Spanned<Expr>, unless: bool, body: &Statement, otherwise: Option<&Statement>, span: Span) -> GenResult<()> {
+        let (then_block, else_block) = self.branch(condition, unless, if otherwise.is_some() { "else" } else { "end" })?;
+
+        self.continue_in(then_block);
+        self.statement(body, span)?;
+
+        let then_end = self.open_block();
+
+        let otherwise = match otherwise {
+            Some(otherwise) => otherwise,
+
+            None => {
+                if then_end.is_some() {
+                    self.builder.br(else_block);
+                }
+
+                self.continue_in(else_block);
+
+                return Ok(())
+            }
+        };
+
+        self.continue_in(else_block);
+        self.statement(otherwise, span)?;
+
+        let else_end = self.open_block();
+
+        if then_end.is_none() && else_end.is_none() {
+            return Ok(())
+        }
+
+        let end = self.new_block("end");
+
+        for block in then_end.into_iter().chain(else_end) {
+            self.builder.move_to_end(block);
+            self.builder.br(end);
+        }
+
+        self.continue_in(end);
+
+        Ok(())
+    }
+
+    // jumps to a new `then` block when the condition holds, or doesn't for `unless`, and to the other block otherwise
+    fn branch(&mut self, condition: &Spanned<Expr>, unless: bool, otherwise: &str) -> GenResult<(BasicBlock, BasicBlock)> {
+        let condition = self.typed(condition, &Type::Bool)?;
+
+        let then_block = self.new_block("then");
+        let else_block = self.new_block(otherwise);
+
+        if unless {
+            self.builder.cond_br(condition, else_block, then_block);
+        } else {
+            self.builder.cond_br(condition, then_block, else_block);
+        }
+
+        Ok((then_block, else_block))
+    }
+
+    fn while_loop(&mut self, condition: &Spanned<Expr>, until: bool, body: &Statement, span: Span) -> GenResult<()> {
+        let head = self.new_block("loop");
+
+        self.builder.br(head);
+        self.continue_in(head);
+
+        let (body_block, exit) = self.branch(condition, until, "after")?;
+
+        self.continue_in(body_block);
+        self.loop_body(head, exit, body, span)?;
+
+        if !self.terminated() {
+            self.builder.br(head);
+        }
+
+        self.continue_in(exit);
+
+        Ok(())
+    }
+
+    // counts through `start..end`, or through the elements of an array of known length
+    fn for_loop(&mut self, name: &str, iterable: &Spanned<Expr>, body: &Statement, span: Span) -> GenResult<()> {
+        self.scopes.push(HashMap::new());
+
+        let result = self.counted_loop(name, iterable, body, span);
+
+        self.scopes.pop();
+
+        result
+    }
+
+    fn counted_loop(&mut self, name: &str, iterable: &Spanned<Expr>, body: &Statement, span: Span) -> GenResult<()> {
+        // a range is counted through by the loop variable itself, an array by a hidden index
+        let (counter, end, array) = match iterable.node {
+            Expr::Range(ref start, ref end) => {
+                let (start, end, ty) = self.operands(start, end, None)?;
+
+                if kind(&ty) != Kind::Integer {
+                    return Err(Spanned::new(CodegenError::Mismatch { expected: Type::Int, found: ty }, iterable.span))
+                }
+
+                (self.declare_local(name, &ty, Some(start), span)?, end, None)
+            }
+
+            _ => {
+                let array = match self.place(iterable)? {
+                    Some(place) => place,
+
+                    // arrays that aren't kept anywhere, like ones returned from calls, are stored for the loop
+                    None => {
+                        let (value, ty) = self.value(iterable, None)?;
+                        let slot        = self.builder.entry_alloca(self.llvm_type(&ty, iterable.span)?, "array");
+
+                        self.builder.store(value, slot);
+
+                        Local {
+                            slot: slot,
+                            ty:   ty,
+                        }
+                    }
+                };
+
+                let len = match array.ty {
+                    Type::Array(_, Some(len)) => len,
+                    ref ty                    => return Err(Spanned::new(CodegenError::NotIterable(ty.clone()), iterable.span)),
+                };
+
+                let index = Local {
+                    slot: self.builder.entry_alloca(llvm_type::int64_type(self.context), "index"),
+                    ty:   Type::Int,
+                };
+
+                let zero = self.integer(0, &Type::Int);
+
+                self.builder.store(zero, index.slot);
+
+                (index, self.integer(len as i64, &Type::Int), Some(array))
+            }
+        };
+
+        let head       = self.new_block("for");
+        let body_block = self.new_block("body");
+        let step       = self.new_block("step");
+        let exit       = self.new_block("after");
+
+        self.builder.br(head);
+        self.continue_in(head);
+
+        let current   = self.builder.load(counter.slot, "counter");
+        let less      = if is_signed(&counter.ty) { IntPredicate::SignedLess } else { IntPredicate::UnsignedLess };
+        let condition = self.builder.icmp(less, current, end, "cmp");
+
+        self.builder.cond_br(condition, body_block, exit);
+
+        self.continue_in(body_block);
+
+        if let Some(Local { slot, ty: Type::Array(ref element, _) }) = array {
+            let zero    = self.integer(0, &Type::Int);
+            let address = self.builder.inbounds_gep(slot, &[zero, current], "element");
+            let value   = self.builder.load(address, name);
+
+            self.declare_local(name, element, Some(value), span)?;
+        }
+
+        self.loop_body(step, exit, body, span)?;
+
+        if !self.terminated() {
+            self.builder.br(step);
+        }
+
+        self.continue_in(step);
+
+        let current = self.builder.load(counter.slot, "counter");
+        let one     = self.integer(1, &counter.ty);
+        let next    = self.builder.add(current, one, "next");
+
+        self.builder.store(next, counter.slot);
+        self.builder.br(head);
+
+        self.continue_in(exit);
+
+        Ok(())
+    }
+
+    fn loop_body(&mut self, next: BasicBlock, exit: BasicBlock, body: &Statement, span: Span) -> GenResult<()> {
+        self.loops.push((next, exit));
+
+        let result = self.statement(body, span);
+
+        self.loops.pop();
+
+        result
+    }
+
+    // an expression that has to be of type `ty`
+    fn typed(&mut self, expr: &Spanned<Expr>, ty: &Type) -> GenResult<Value> {
+        let (value, found) = self.expr(expr, Some(ty))?;
+
+        if found != *ty {
+            return Err(Spanned::new(CodegenError::Mismatch { expected: ty.clone(), found: found }, expr.span))
+        }
+
+        Ok(value)
+    }
+
+    // an expression that has to produce a value, unlike calls to functions returning `void`
+    fn value(&mut self, expr: &Spanned<Expr>, expected: Option<&Type>) -> GenResult<(Value, Type)> {
+        let (value, ty) = self.expr(expr, expected)?;
+
+        if ty == Type::Void {
+            return Err(Spanned::new(CodegenError::NoValue, expr.span))
+        }
+
+        Ok((value, ty))
+    }
+
+    // `expected` is only a hint, for numbers without a suffix to take their type from
+    fn expr(&mut self, expr: &Spanned<Expr>, expected: Option<&Type>) -> GenResult<(Value, Type)> {
+        let span = expr.span;
+
+        match expr.node {
+            // literals are never negative, their bits are read back as a magnitude
+            Expr::Integer(n, t) => self.literal(n as u64 as i128, t, expected, span),
+
+            Expr::Float(n, t) => {
+                let ty = match (t, expected) {
+                    (Some(t), _)                                => Type::Number(t),
+                    (None, Some(ty)) if kind(ty) == Kind::Float => ty.clone(),
+                    (None, _)                                   => Type::Float,
+                };
+
+                Ok((self.float(n, &ty), ty))
+            }
+
+            Expr::True  => Ok((true.to_representation(self.context), Type::Bool)),
+            Expr::False => Ok((false.to_representation(self.context), Type::Bool)),
+
+            Expr::Identifier(ref name) => match self.local(name) {
+                Some(local) => Ok((self.builder.load(local.slot, name), local.ty)),
+
+                None if self.signatures.contains_key(name) => Err(Spanned::new(CodegenError::Unsupported("Functions as values"), span)),
+                None                                      => Err(Spanned::new(CodegenError::UnknownVariable(name.clone()), span)),
+            },
+
+            Expr::FnCall(ref name, ref args) => self.call(name, args, span, false),
+
+            Expr::Assignment(ref target, ref value) => {
+                let local = self.assignable(target)?;
+                let value = self.typed(value, &local.ty)?;
+
+                self.builder.store(value, local.slot);
+
+                Ok((value, local.ty))
+            }
+
+            Expr::CompoundAssignment(op, ref target, ref value) => {
+                let local   = self.assignable(target)?;
+                let current = self.builder.load(local.slot, "current");
+                let value   = self.typed(value, &local.ty)?;
+
+                let (result, _) = self.binary(op, current, value, &local.ty, span)?;
+
+                self.builder.store(result, local.slot);
+
+                Ok((result, local.ty))
+            }
+
+            Expr::Binary(ref lhs, BinaryOp::And, ref rhs) => self.short_circuit(lhs, false, rhs),
+            Expr::Binary(ref lhs, BinaryOp::Or, ref rhs)  => self.short_circuit(lhs, true, rhs),
+
+            Expr::Binary(ref lhs, op, ref rhs) => {
+                // only arithmetic gives back the type of its operands
+                let hint = if comparison(op, true).is_none() { expected } else { None };
+
+                let (lhs, rhs, ty) = self.operands(lhs, rhs, hint)?;
+
+                self.binary(op, lhs, rhs, &ty, span)
+            }
+
+            Expr::Unary(op, ref operand) => {
+                // a negated literal is a negative number, checked against its type as one
+                if let Expr::Integer(n, t) = operand.node {
+                    if op == UnaryOp::Negate {
+                        return self.literal(-(n as u64 as i128), t, expected, span)
+                    }
+                }
+
+                let (value, ty) = self.value(operand, expected)?;
+
+                let result = match (op, kind(&ty)) {
+                    (UnaryOp::Negate, Kind::Integer) => self.builder.neg(value, "neg"),
+                    (UnaryOp::Negate, Kind::Float)   => self.builder.fneg(value, "neg"),
+                    (UnaryOp::Not, Kind::Bool)       |
+                    (UnaryOp::Not, Kind::Integer)    => self.builder.not(value, "not"),
+
+                    _ => return Err(Spanned::new(CodegenError::InvalidOperand(op, ty), span)),
+                };
+
+                Ok((result, ty))
+            }
+
+            Expr::Index(ref object, ref index) => {
+                let element = self.element(object, index)?;
+
+                Ok((self.builder.load(element.slot, "element"), element.ty))
+            }
+
+            Expr::Conditional(ref c) => self.conditional_value(c, expected, span),
+
+            Expr::Range(..) => Err(Spanned::new(CodegenError::StrayRange, span)),
+
+            Expr::Text(ref text)           => Ok((self.builder.global_string(text, "text"), Type::Str)),
+            Expr::Interpolation(ref parts) => self.interpolation(parts),
+
+            Expr::Dot(..)            => Err(Spanned::new(CodegenError::Unsupported("Fields"), span)),
+            Expr::Lambda(ref lambda) => self.lambda(lambda, span),
+        }
+    }
+
+    // `&&` only looks at its right side when the left holds, `||` when it doesn't
+    fn short_circuit(&mut self, lhs: &Spanned<Expr>, or: bool, rhs: &Spanned<Expr>) -> GenResult<(Value, Type)> {
+        let lhs     = self.typed(lhs, &Type::Bool)?;
+        let lhs_end = self.builder.insert_block();
+
+        let rhs_block = self.new_block("rhs");
+        let end       = self.new_block("end");
+
+        if or {
+            self.builder.cond_br(lhs, end, rhs_block);
+        } else {
+            self.builder.cond_br(lhs, rhs_block, end);
+        }
+
+        self.continue_in(rhs_block);
+
+        let rhs     = self.typed(rhs, &Type::Bool)?;
+        let rhs_end = self.builder.insert_block();
+
+        self.builder.br(end);
+        self.continue_in(end);
+
+        let incoming = [(lhs, lhs_end), (rhs, rhs_end)];
+
+        Ok((self.builder.phi(llvm_type::int1_type(self.context), &incoming, if or { "or" } else { "and" }), Type::Bool))
+    }
+
+    // a conditional used as a value, which is the last expression of the branch taken
+    fn conditional_value(&mut self, conditional: &Statement, expected: Option<&Type>, span: Span) -> GenResult<(Value, Type)> {
+        let (condition, unless, body, otherwise) = match *conditional {
+            Statement::IfElse(ref c, ref body, ref otherwise)     => (c, false, body, otherwise),
+            Statement::UnlessElse(ref c, ref body, ref otherwise) => (c, true, body, otherwise),
+            _                                                     => return Err(Spanned::new(CodegenError::MissingElse, span)),
+        };
+
+        let (then_block, else_block) = self.branch(condition, unless, "else")?;
+
+        self.continue_in(then_block);
+
+        let (then_value, ty) = self.branch_value(body, expected, span)?;
+        let then_end         = self.builder.insert_block();
+
+        self.continue_in(else_block);
+
+        // `else if` goes on with another conditional rather than a block
+        let (else_value, found) = match **otherwise {
+            Statement::Block(_) => self.branch_value(otherwise, Some(&ty), span)?,
+            _                   => self.conditional_value(otherwise, Some(&ty), span)?,
+        };
+
+        if found != ty {
+            return Err(Spanned::new(CodegenError::Mismatch { expected: ty, found: found }, span))
+        }
+
+        let else_end = self.builder.insert_block();
+        let end      = self.new_block("end");
+
+        self.builder.br(end);
+        self.builder.move_to_end(then_end);
+        self.builder.br(end);
+        self.continue_in(end);
+
+        let incoming = [(then_value, then_end), (else_value, else_end)];
+
+        Ok((self.builder.phi(self.llvm_type(&ty, span)?, &incoming, "value"), ty))
+    }
+
+    // the statements of a branch, then the value of the expression it ends in
+    fn branch_value(&mut self, body: &Statement, expected: Option<&Type>, span: Span) -> GenResult<(Value, Type)> {
+        let statements = match *body {
+            Statement::Block(ref statements) => statements,
+            _                                => return Err(Spanned::new(CodegenError::NoBranchValue, span)),
+        };
+
+        let (last, rest) = match statements.split_last() {
+            Some((&Spanned { node: Statement::Expr(ref e), .. }, rest)) => (e, rest),
+            Some((last, _))                                            => return Err(Spanned::new(CodegenError::NoBranchValue, last.span)),
+            None                                                       => return Err(Spanned::new(CodegenError::NoBranchValue, span)),
+        };
+
+        self.scopes.push(HashMap::new());
+
+        let result = rest.iter().map(|s| self.statement(&s.node, s.span)).collect::<GenResult<()>>().and_then(|_| {
+            // a branch that returns or leaves a loop halfway has nothing to give
+            if self.terminated() {
+                Err(Spanned::new(CodegenError::NoBranchValue, last.span))
+            } else {
+                self.value(last, expected)
+            }
+        });
+
+        self.scopes.pop();
+
+        result
+    }
+
+    // both sides of an operator, which have to be of the same type.
+    // a bare number on the left takes its type from the right
+    fn operands(&mut self, lhs: &Spanned<Expr>, rhs: &Spanned<Expr>, hint: Option<&Type>) -> GenResult<(Value, Value, Type)> {
+        if is_untyped(lhs) && !is_untyped(rhs) {
+            let (rhs, ty) = self.value(rhs, hint)?;
+
+            Ok((self.typed(lhs, &ty)?, rhs, ty))
+        } else {
+            let (lhs, ty) = self.value(lhs, hint)?;
+
+            Ok((lhs, self.typed(rhs, &ty)?, ty))
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Spanned<Expr>], span: Span, tail: bool) -> GenResult<(Value, Type)> {
+        if let Some(local) = self.local(name) {
+            return self.closure_call(name, local, args, span, tail)
+        }
+
+        let (function, signature) = match (self.module.get_function(name), self.signatures.get(name)) {
+            (Some(function), Some(signature)) => (function, signature.clone()),
+            _                                 => return Err(Spanned::new(CodegenError::UnknownFunction(name.to_string()), span)),
+        };
+
+        if args.len() != signature.params.len() {
+            return Err(Spanned::new(CodegenError::WrongArguments {
+                name:     name.to_string(),
+                expected: signature.params.len(),
+                found:    args.len(),
+            }, span))
+        }
+
+        let mut values = Vec::new();
+
+        for (arg, ty) in args.iter().zip(&signature.params) {
+            values.push(self.typed(arg, ty)?);
+        }
+
+        let name = if signature.ret == Type::Void { "" } else { name };
+
+        let call = if tail {
+            self.builder.tail_call(function, &values, name)
+        } else {
+            self.builder.call(function, &values, name)
+        };
+
+        // the arguments were checked against the signature above, so LLVM agrees with them
+        Ok((call.expect("arguments don't match the declaration"), signature.ret))
+    }
+
+    // calls the closure a variable holds, passing its environment ahead of the arguments
+    fn closure_call(&mut self, name: &str, local: Local, args: &[Spanned<Expr>], span: Span, tail: bool) -> GenResult<(Value, Type)> {
+        let (params, ret) = match local.ty {
+            Type::Function(ref params, ref ret) => (params.clone(), (**ret).clone()),
+            ref ty                              => return Err(Spanned::new(CodegenError::NotCallable(ty.clone()), span)),
+        };
+
+        if args.len() != params.len() {
+            return Err(Spanned::new(CodegenError::WrongArguments {
+                name:     name.to_string(),
+                expected: params.len(),
+                found:    args.len(),
+            }, span))
+        }
+
+        let mut values = Vec::new();
+
+        for (arg, ty) in args.iter().zip(&params) {
+            values.push(self.typed(arg, ty)?);
+        }
+
+        let closure     = self.builder.load(local.slot, name);
+        let code        = self.builder.extract_value(closure, 0, "code");
+        let environment = self.builder.extract_value(closure, 1, "environment");
+
+        values.insert(0, environment);
+
+        let name = if ret == Type::Void { "" } else { name };
+
+        let call = if tail {
+            self.builder.tail_call_pointer(code, &values, name)
+        } else {
+            self.builder.call_pointer(code, &values, name)
+        };
+
+        // the arguments were checked against the variable's type, which the closure was made with
+        Ok((call.expect("arguments don't match the closure"), ret))
+    }
+
+    // a lambda becomes a function of its own, taking an environment ahead of its parameters: a struct on
+    // the heap holding copies of the variables it captures, as they were when it was made. the lambda's
+    // value is a closure, the function's address along with its environment
+    fn lambda(&mut self, lambda: &Lambda, span: Span) -> GenResult<(Value, Type)> {
+        let context = self.context;
+        let opaque  = llvm_type::pointer_type(llvm_type::int8_type(context), 0);
+
+        // other names are functions, or unknown, which the body reports
+        let captures = lambda.captures.iter()
+                                      .filter_map(|name| self.local(name).map(|local| (name.clone(), local)))
+                                      .collect::<Vec<_>>();
+
+        let mut fields = Vec::new();
+
+        for (_, local) in &captures {
+            fields.push(self.llvm_type(&local.ty, span)?);
+        }
+
+        let environment_type = llvm_type::environment_type(context, &mut fields);
+
+        let mut params = vec![opaque];
+
+        for param in &lambda.params {
+            params.push(self.llvm_type(&param.ty, span)?);
+        }
+
+        let ret      = self.llvm_type(&lambda.ret, span)?;
+        let function = function::Function::new(&self.module, "lambda", &mut params, ret);
+
+        function.set_internal();
+
+        let environment = if captures.is_empty() {
+            llvm_type::null(opaque)
+        } else {
+            let malloc      = self.runtime("malloc");
+            let environment = self.builder.call(malloc, &[llvm_type::size_of(environment_type)], "environment");
+            let environment = environment.expect("`malloc` was called wrongly");
+            let fields      = self.builder.bitcast(environment, llvm_type::pointer_type(environment_type, 0), "captures");
+
+            for (i, (name, local)) in captures.iter().enumerate() {
+                let value = self.builder.load(local.slot, name);
+                let field = self.builder.struct_gep(fields, i as u32, name);
+
+                self.builder.store(value, field);
+            }
+
+            environment
+        };
+
+        // the body is generated as a function of its own, then the enclosing one goes on where it was
+        let block  = self.builder.insert_block();
+        let ret    = mem::replace(&mut self.ret, lambda.ret.clone());
+        let scopes = mem::replace(&mut self.scopes, vec![HashMap::new()]);
+        let loops  = mem::take(&mut self.loops);
+
+        let result = self.lambda_body(lambda, function, environment_type, &captures, span);
+
+        self.builder.move_to_end(block);
+
+        self.ret    = ret;
+        self.scopes = scopes;
+        self.loops  = loops;
+
+        result?;
+
+        let ty      = Type::Function(lambda.params.iter().map(|p| p.ty.clone()).collect(), Box::new(lambda.ret.clone()));
+        let closure = llvm_type::undef(self.llvm_type(&ty, span)?);
+        let closure = self.builder.insert_value(closure, Value::from_ref(function.to_ref()), 0, "closure");
+        let closure = self.builder.insert_value(closure, environment, 1, "closure");
+
+        Ok((closure, ty))
+    }
+
+    fn lambda_body(&mut self, lambda: &Lambda, function: function::Function, environment_type: LLVMTypeRef,
+                   captures: &[(String, Local)], span: Span) -> GenResult<()> {
+        self.builder.move_to_end(function.new_basic_block("entry"));
+
+        // captures are copied out of the environment into variables, like parameters
+        if !captures.is_empty() {
+            let fields = self.builder.bitcast(function.param(0), llvm_type::pointer_type(environment_type, 0), "captures");
+
+            for (i, (name, local)) in captures.iter().enumerate() {
+                let field = self.builder.struct_gep(fields, i as u32, name);
+                let value = self.builder.load(field, name);
+
+                self.declare_local(name, &local.ty, Some(value), span)?;
+            }
+        }
+
+        for (i, param) in lambda.params.iter().enumerate() {
+            let value = function.param(i as u32 + 1);
+
+            self.declare_local(&param.name, &param.ty, Some(value), span)?;
+        }
+
+        self.statement(&lambda.body, span)?;
+
+        if !self.terminated() {
+            if lambda.ret != Type::Void {
+                return Err(Spanned::new(CodegenError::MissingReturn("lambda".to_string()), span))
+            }
+
+            self.builder.return_void();
+        }
+
+        Ok(())
+    }
+
+    // formats the parts with `snprintf` into a string of their own, measuring it first to know how much
+    // to allocate. strings are never freed
+    fn interpolation(&mut self, parts: &[InterpolationPart]) -> GenResult<(Value, Type)> {
+        let mut format = String::new();
+        let mut values = Vec::new();
+
+        for part in parts {
+            match *part {
+                InterpolationPart::Literal(ref text) => format.push_str(&text.replace('%', "%%")),
+
+                InterpolationPart::Expr(ref expr) => {
+                    let (value, ty)         = self.value(expr, None)?;
+                    let (conversion, value) = self.formatted(value, &ty, expr.span)?;
+
+                    format.push_str(conversion);
+                    values.push(value);
+                }
+            }
+        }
+
+        let context  = self.context;
+        let snprintf = self.runtime("snprintf");
+        let format   = self.builder.global_string(&format, "format");
+        let text     = llvm_type::pointer_type(llvm_type::int8_type(context), 0);
+
+        let mut args = vec![llvm_type::null(text), 0i64.to_representation(context), format];
+        args.extend_from_slice(&values);
+
+        // the arguments are laid out as `RUNTIME` functions are declared
+        let length = self.builder.call(snprintf, &args, "length").expect("`snprintf` was called wrongly");
+        let length = self.builder.sext(length, llvm_type::int64_type(context), "length");
+        let size   = self.builder.add(length, 1i64.to_representation(context), "size");
+        let malloc = self.runtime("malloc");
+        let buffer = self.builder.call(malloc, &[size], "text").expect("`malloc` was called wrongly");
+
+        args[0] = buffer;
+        args[1] = size;
+
+        self.builder.call(snprintf, &args, "").expect("`snprintf` was called wrongly");
+
+        Ok((buffer, Type::Str))
+    }
+
+    // the `printf` conversion a value is written with, and the value as C's variadic functions take it:
+    // integers widened to 64 bits and floats to doubles
+    fn formatted(&mut self, value: Value, ty: &Type, span: Span) -> GenResult<(&'static str, Value)> {
+        let context = self.context;
+
+        Ok(match *ty {
+            Type::Int                                   => ("%lld", value),
+            Type::Float | Type::Number(NumberType::F64) => ("%g", value),
+            Type::Str                                   => ("%s", value),
+
+            Type::Number(NumberType::F32) => ("%g", self.builder.fpext(value, llvm_type::double_type(context), "widened")),
+            Type::Number(NumberType::I64) => ("%lld", value),
+            Type::Number(NumberType::U64) => ("%llu", value),
+            Type::Number(t) if t.is_signed() => ("%lld", self.builder.sext(value, llvm_type::int64_type(context), "widened")),
+            Type::Number(_)                  => ("%llu", self.builder.zext(value, llvm_type::int64_type(context), "widened")),
+
+            Type::Bool => {
+                let yes = self.builder.global_string("true", "true");
+                let no  = self.builder.global_string("false", "false");
+
+                ("%s", self.builder.select(value, yes, no, "bool"))
+            }
+
+            _ => return Err(Spanned::new(CodegenError::NotFormattable(ty.clone()), span)),
+        })
+    }
+
+    // one of the `RUNTIME` functions, declared the first time it's needed. `size_t` is taken to be 64 bits
+    fn runtime(&self, name: &str) -> function::Function {
+        if let Some(function) = self.module.get_function(name) {
+            return function
+        }
+
+        let context = self.context;
+        let text    = llvm_type::pointer_type(llvm_type::int8_type(context), 0);
+        let size    = llvm_type::int64_type(context);
+
+        match name {
+            "malloc" => function::Function::new(&self.module, name, &mut [size], text),
+            _        => function::Function::new_variadic(&self.module, name, &mut [text, size, text], llvm_type::int32_type(context)),
+        }
+    }
+
+    // what an assignment stores to
+    fn assignable(&mut self, target: &Spanned<Expr>) -> GenResult<Local> {
+        match self.place(target)? {
+            Some(place) => Ok(place),
+
+            None => match target.node {
+                Expr::Identifier(ref name) => Err(Spanned::new(CodegenError::UnknownVariable(name.clone()), target.span)),
+                _                          => Err(Spanned::new(CodegenError::Unsupported("Fields"), target.span)),
+            },
+        }
+    }
+
+    // the address of an expression naming memory, a variable or an element of an array
+    fn place(&mut self, expr: &Spanned<Expr>) -> GenResult<Option<Local>> {
+        match expr.node {
+            Expr::Identifier(ref name)         => Ok(self.local(name)),
+            Expr::Index(ref object, ref index) => self.element(object, index).map(Some),
+            _                                  => Ok(None),
+        }
+    }
+
+    // arrays of known length are indexed where they're kept, `[]` arrays and pointers through the address they hold
+    fn element(&mut self, object: &Spanned<Expr>, index: &Spanned<Expr>) -> GenResult<Local> {
+        let (base, ty) = match self.place(object)? {
+            Some(Local { slot, ty: Type::Array(ref element, Some(_)) }) => {
+                let zero  = self.integer(0, &Type::Int);
+                let index = self.typed(index, &Type::Int)?;
+
+                return Ok(Local {
+                    slot: self.builder.inbounds_gep(slot, &[zero, index], "element"),
+                    ty:   (**element).clone(),
+                })
+            }
+
+            Some(place) => (self.builder.load(place.slot, "array"), place.ty),
+            None        => self.value(object, None)?,
+        };
+
+        let element = match ty {
+            Type::Pointer(ref element) if **element != Type::Void => (**element).clone(),
+            Type::Array(ref element, None)                        => (**element).clone(),
+
+            _ => return Err(Spanned::new(CodegenError::NotIndexable(ty.clone()), object.span)),
+        };
+
+        let index = self.typed(index, &Type::Int)?;
+
+        Ok(Local {
+            slot: self.builder.gep(base, &[index], "element"),
+            ty:   element,
+        })
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: Value, rhs: Value, ty: &Type, span: Span) -> GenResult<(Value, Type)> {
+        let kind   = kind(ty);
+        let signed = is_signed(ty);
+
+        if let Some((int, real)) = comparison(op, signed) {
+            let equality = op == BinaryOp::Equal || op == BinaryOp::NotEqual;
+
+            let result = match kind {
+                Kind::Integer          => self.builder.icmp(int, lhs, rhs, "cmp"),
+                Kind::Bool if equality => self.builder.icmp(int, lhs, rhs, "cmp"),
+                Kind::Float            => self.builder.fcmp(real, lhs, rhs, "cmp"),
+
+                _ => return Err(Spanned::new(CodegenError::InvalidOperands(op, ty.clone()), span)),
+            };
+
+            return Ok((result, Type::Bool))
+        }
+
+        let result = match (op, kind) {
+            (BinaryOp::Add, Kind::Integer)           => self.builder.add(lhs, rhs, "add"),
+            (BinaryOp::Sub, Kind::Integer)           => self.builder.sub(lhs, rhs, "sub"),
+            (BinaryOp::Mul, Kind::Integer)           => self.builder.mul(lhs, rhs, "mul"),
+            (BinaryOp::Div, Kind::Integer) if signed => self.builder.sdiv(lhs, rhs, "div"),
+            (BinaryOp::Div, Kind::Integer)           => self.builder.udiv(lhs, rhs, "div"),
+            (BinaryOp::Mod, Kind::Integer) if signed => self.builder.srem(lhs, rhs, "rem"),
+            (BinaryOp::Mod, Kind::Integer)           => self.builder.urem(lhs, rhs, "rem"),
+
+            (BinaryOp::Add, Kind::Float) => self.builder.fadd(lhs, rhs, "add"),
+            (BinaryOp::Sub, Kind::Float) => self.builder.fsub(lhs, rhs, "sub"),
+            (BinaryOp::Mul, Kind::Float) => self.builder.fmul(lhs, rhs, "mul"),
+            (BinaryOp::Div, Kind::Float) => self.builder.fdiv(lhs, rhs, "div"),
+            (BinaryOp::Mod, Kind::Float) => self.builder.frem(lhs, rhs, "rem"),
+
+            (BinaryOp::BitAnd, Kind::Integer) | (BinaryOp::BitAnd, Kind::Bool) => self.builder.and(lhs, rhs, "and"),
+            (BinaryOp::BitOr, Kind::Integer)  | (BinaryOp::BitOr, Kind::Bool)  => self.builder.or(lhs, rhs, "or"),
+            (BinaryOp::BitXor, Kind::Integer) | (BinaryOp::BitXor, Kind::Bool) => self.builder.xor(lhs, rhs, "xor"),
+
+            (BinaryOp::ShiftLeft, Kind::Integer)            => self.builder.shl(lhs, rhs, "shl"),
+            (BinaryOp::ShiftRight, Kind::Integer) if signed => self.builder.ashr(lhs, rhs, "shr"),
+            (BinaryOp::ShiftRight, Kind::Integer)           => self.builder.lshr(lhs, rhs, "shr"),
+
+            (BinaryOp::Pow, _) => return Err(Spanned::new(CodegenError::Unsupported("Powers"), span)),
+
+            _ => return Err(Spanned::new(CodegenError::InvalidOperands(op, ty.clone()), span)),
+        };
+
+        Ok((result, ty.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parser::parser::parse_program;
+    use vm::engine::Engine;
+
+    // generates and verifies a module from one program, which must be free of errors
+    fn compile(context: &Context, source: &str) -> Module {
+        let (program, errors) = parse_program(source);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let mut generator = Generator::new(context, "test");
+        let mut errors    = generator.declare(&program);
+
+        errors.extend(generator.define(&program));
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let module = generator.finish().unwrap();
+
+        module.verify().unwrap();
+        module
+    }
+
+    // what `main` returns
+    fn run(source: &str) -> i32 {
+        let context = Context::new();
+        let engine  = Engine::new(compile(&context, source)).unwrap();
+
+        engine.run("main").unwrap()
+    }
+
+    fn errors(source: &str) -> Vec<CodegenError> {
+        let (program, errors) = parse_program(source);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let context       = Context::new();
+        let mut generator = Generator::new(&context, "test");
+        let mut errors    = generator.declare(&program);
+
+        errors.extend(generator.define(&program));
+        errors.into_iter().map(|e| e.node).collect()
+    }
+
+    #[test]
+    fn fib_example() {
+        let context = Context::new();
+        let module  = compile(&context, include_str!("../../examples/fib.ice"));
+        let fib     = module.get_function("fib").unwrap();
+
+        assert_eq!(fib.arity(), 1);
+        assert_eq!(run(&format!("{}\nif fib(10) == 89\n    return 0\nend\nreturn 1\n", include_str!("../../examples/fib.ice"))), 0);
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(run("
+func add (a: int, b: int) -> int
+    return a + b
+end
+
+func double (a: i32) -> i32
+    return a * 2
+end
+
+x: int = add 1, add(2, 3)
+if x == 6
+    return double 5
+end
+return 1
+"), 10);
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(run("
+func sign (a: int) -> int
+    if a < 0
+        return -1
+    else if a == 0
+        return 0
+    end
+    return 1
+end
+
+unless sign(-5) == -1
+    return 1
+end
+x: i32 = if sign(0) == 0
+    7
+else
+    8
+end
+return x
+"), 7);
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(run("
+total: int = 0
+for i in 0..10
+    if i % 2 == 0
+        continue
+    end
+    total += i
+end
+
+i: i32 = 0
+until i >= 100
+    i += 1
+    if i == 5
+        break
+    end
+end
+if total == 25
+    return i
+end
+return 1
+"), 5);
+    }
+
+    #[test]
+    fn logic_short_circuits() {
+        // dividing by zero would trap if the right side were evaluated
+        assert_eq!(run("
+zero: int = 0
+if zero ~= 0 && 10 / zero > 1
+    return 1
+end
+if zero == 0 || 10 / zero > 1
+    return 5
+end
+return 2
+"), 5);
+    }
+
+    #[test]
+    fn closures() {
+        assert_eq!(run("
+func adder (n: i32) -> func (i32) -> i32
+    return lambda (x: i32) -> i32
+        return x + n
+    end
+end
+
+func apply (f: func (i32) -> i32, x: i32) -> i32
+    return f(x)
+end
+
+base: i32 = 10
+scaled: func (i32) -> i32 = lambda (x: i32) -> i32
+    return x * 3 + base
+end
+base = 1000
+
+return apply(scaled, 4) + apply(adder(5), 100)
+"), 127);
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        assert_eq!(errors("return missing 1\n"), vec![CodegenError::UnknownFunction("missing".to_string())]);
+        assert_eq!(errors("x: u8 = 256\n"), vec![CodegenError::OutOfRange { value: "256".to_string(), ty: Type::Number(NumberType::U8) }]);
+    }
+}
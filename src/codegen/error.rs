@@ -0,0 +1,99 @@
+use parser::parser::{Type, BinaryOp, UnaryOp};
+use parser::span::Spanned;
+use parser::diagnostic::Diagnostic;
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodegenError {
+    UnknownVariable(String),
+    UnknownFunction(String),
+    UnknownType(String),
+    Redefined(String),
+    Reserved(String),
+    Mismatch { expected: Type, found: Type },
+    WrongArguments { name: String, expected: usize, found: usize },
+    NotCallable(Type),
+    OutOfRange { value: String, ty: Type },
+    InvalidOperands(BinaryOp, Type),
+    InvalidOperand(UnaryOp, Type),
+    MissingReturn(String),
+    NoValue,
+    InvalidMain,
+    MainWithStatements,
+    StrayRange,
+    NotIndexable(Type),
+    NotIterable(Type),
+    NotFormattable(Type),
+    MissingElse,
+    NoBranchValue,
+    Unsupported(&'static str),
+}
+
+impl CodegenError {
+    // a short note to put under the offending source
+    pub fn label(&self) -> String {
+        match *self {
+            CodegenError::UnknownVariable(_)             => "not found in this scope".to_string(),
+            CodegenError::UnknownFunction(_)             => "not a function".to_string(),
+            CodegenError::UnknownType(_)                 => "unknown type".to_string(),
+            CodegenError::Redefined(_)                   => "defined again here".to_string(),
+            CodegenError::Reserved(_)                    => "reserved name".to_string(),
+            CodegenError::Mismatch { ref expected, .. }  => format!("expected `{}`", expected),
+            CodegenError::WrongArguments { expected, .. } => format!("expected {} argument{}", expected, if expected == 1 { "" } else { "s" }),
+            CodegenError::NotCallable(_)                 => "not a function".to_string(),
+            CodegenError::OutOfRange { ref ty, .. }      => format!("out of range for `{}`", ty),
+            CodegenError::InvalidOperands(..)            => "invalid operands".to_string(),
+            CodegenError::InvalidOperand(..)             => "invalid operand".to_string(),
+            CodegenError::MissingReturn(_)               => "may end without returning".to_string(),
+            CodegenError::NoValue                        => "returns `void`".to_string(),
+            CodegenError::InvalidMain                    => "expected `func main () -> i32`".to_string(),
+            CodegenError::MainWithStatements             => "top-level statement".to_string(),
+            CodegenError::StrayRange                     => "not in a `for` loop".to_string(),
+            CodegenError::NotIndexable(_)                => "not an array or pointer".to_string(),
+            CodegenError::NotIterable(_)                 => "expected a range or an array of known length".to_string(),
+            CodegenError::NotFormattable(_)              => "expected a number, `bool` or `str`".to_string(),
+            CodegenError::MissingElse                    => "has no `else`".to_string(),
+            CodegenError::NoBranchValue                  => "expected an expression".to_string(),
+            CodegenError::Unsupported(_)                 => "not supported yet".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodegenError::UnknownVariable(ref name)             => write!(f, "Unknown variable `{}`", name),
+            CodegenError::UnknownFunction(ref name)             => write!(f, "Unknown function `{}`", name),
+            CodegenError::UnknownType(ref name)                 => write!(f, "Unknown type `{}`", name),
+            CodegenError::Redefined(ref name)                   => write!(f, "`{}` is defined more than once", name),
+            CodegenError::Reserved(ref name)                    => write!(f, "`{}` is used by the runtime and can't be defined", name),
+            CodegenError::Mismatch { ref expected, ref found }  => write!(f, "Expected a value of type `{}`, found `{}`", expected, found),
+            CodegenError::WrongArguments { ref name, expected, found } => write!(f, "`{}` takes {} argument{} but was given {}", name, expected, if expected == 1 { "" } else { "s" }, found),
+            CodegenError::NotCallable(ref t)                    => write!(f, "`{}` can't be called", t),
+            CodegenError::OutOfRange { ref value, ref ty }      => write!(f, "`{}` doesn't fit in `{}`", value, ty),
+            CodegenError::InvalidOperands(op, ref t)            => write!(f, "`{}` can't be applied to `{}`", op, t),
+            CodegenError::InvalidOperand(op, ref t)             => write!(f, "`{}` can't be applied to `{}`", op, t),
+            CodegenError::MissingReturn(ref name)               => write!(f, "`{}` may end without returning a value", name),
+            CodegenError::NoValue                               => write!(f, "Expected a value, found nothing"),
+            CodegenError::InvalidMain                           => write!(f, "`main` must take no parameters and return `i32`"),
+            CodegenError::MainWithStatements                    => write!(f, "Top-level statements can't be used alongside a `main` function"),
+            CodegenError::StrayRange                            => write!(f, "Ranges can only be looped over"),
+            CodegenError::NotIndexable(ref t)                   => write!(f, "`{}` can't be indexed", t),
+            CodegenError::NotIterable(ref t)                    => write!(f, "`{}` can't be looped over", t),
+            CodegenError::NotFormattable(ref t)                 => write!(f, "`{}` can't be put in a string", t),
+            CodegenError::MissingElse                           => write!(f, "A conditional used as a value needs an `else`"),
+            CodegenError::NoBranchValue                         => write!(f, "Each branch of a conditional used as a value has to end in an expression"),
+            CodegenError::Unsupported(what)                     => write!(f, "{} aren't supported yet", what),
+        }
+    }
+}
+
+impl Error for CodegenError {}
+
+impl<'a> From<&'a Spanned<CodegenError>> for Diagnostic {
+    fn from(error: &'a Spanned<CodegenError>) -> Diagnostic {
+        Diagnostic::new(error.node.to_string(), error.node.label(), error.span)
+    }
+}
@@ -0,0 +1,482 @@
+use parser::lexer::{Token, TextPart, NumberType};
+use parser::parser::{Program, Item, Function, Lambda, Param, Statement, Expr, InterpolationPart};
+use parser::span::{Span, Spanned};
+
+// a machine-readable view of tokens and syntax trees, written out as either JSON or S-expressions.
+// nodes have a kind, a span when they carry one, and named fields in a fixed order
+pub enum Dump {
+    Null,
+    Bool(bool),
+    // kept as written, so 64-bit values survive
+    Number(String),
+    Str(String),
+    List(Vec<Dump>),
+    Node(&'static str, Option<Span>, Vec<(&'static str, Dump)>),
+}
+
+impl Dump {
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+
+        self.write_json(&mut out, 0);
+        out.push('\n');
+
+        out
+    }
+
+    pub fn to_sexp(&self) -> String {
+        let mut out = String::new();
+
+        self.write_sexp(&mut out, 0);
+        out.push('\n');
+
+        out
+    }
+
+    // the same tree without its spans, for comparing trees parsed from different sources
+    #[cfg(test)]
+    pub fn without_spans(self) -> Dump {
+        match self {
+            Dump::List(items)           => Dump::List(items.into_iter().map(Dump::without_spans).collect()),
+            Dump::Node(kind, _, fields) => Dump::Node(kind, None, fields.into_iter().map(|(name, value)| (name, value.without_spans())).collect()),
+            other                       => other,
+        }
+    }
+
+    fn write_json(&self, out: &mut String, level: usize) {
+        match *self {
+            Dump::Null          => out.push_str("null"),
+            Dump::Bool(b)       => out.push_str(if b { "true" } else { "false" }),
+            Dump::Number(ref n) => out.push_str(n),
+            Dump::Str(ref s)    => out.push_str(&quote(s)),
+
+            Dump::List(ref items) => {
+                if items.is_empty() {
+                    return out.push_str("[]")
+                }
+
+                out.push('[');
+
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(if i > 0 { ",\n" } else { "\n" });
+                    indent(out, level + 1);
+                    item.write_json(out, level + 1);
+                }
+
+                out.push('\n');
+                indent(out, level);
+                out.push(']');
+            }
+
+            Dump::Node(kind, span, ref fields) => {
+                out.push_str("{\n");
+                indent(out, level + 1);
+                out.push_str(&format!("\"kind\": {}", quote(kind)));
+
+                if let Some(span) = span {
+                    out.push_str(",\n");
+                    indent(out, level + 1);
+                    out.push_str(&format!(
+                        "\"span\": {{ \"start\": {}, \"end\": {}, \"line\": {}, \"column\": {} }}",
+                        span.start, span.end, span.line, span.column,
+                    ));
+                }
+
+                for &(name, ref value) in fields {
+                    out.push_str(",\n");
+                    indent(out, level + 1);
+                    out.push_str(&format!("{}: ", quote(name)));
+                    value.write_json(out, level + 1);
+                }
+
+                out.push('\n');
+                indent(out, level);
+                out.push('}');
+            }
+        }
+    }
+
+    // `(kind :span (start end line column) :field value ...)`, nested nodes and lists start new lines
+    fn write_sexp(&self, out: &mut String, level: usize) {
+        match *self {
+            Dump::Null          => out.push_str("nil"),
+            Dump::Bool(b)       => out.push_str(if b { "#t" } else { "#f" }),
+            Dump::Number(ref n) => out.push_str(n),
+            Dump::Str(ref s)    => out.push_str(&quote(s)),
+
+            Dump::List(ref items) => {
+                out.push('(');
+
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push('\n');
+                        indent(out, level + 1);
+                    }
+
+                    item.write_sexp(out, level + 1);
+                }
+
+                out.push(')');
+            }
+
+            Dump::Node(kind, span, ref fields) => {
+                out.push('(');
+                out.push_str(kind);
+
+                if let Some(span) = span {
+                    out.push_str(&format!(" :span ({} {} {} {})", span.start, span.end, span.line, span.column));
+                }
+
+                for &(name, ref value) in fields {
+                    match *value {
+                        Dump::Node(..) | Dump::List(_) => {
+                            out.push('\n');
+                            indent(out, level + 1);
+                        }
+
+                        _ => out.push(' '),
+                    }
+
+                    out.push_str(&format!(":{} ", name));
+                    value.write_sexp(out, level + 1);
+                }
+
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn indent(out: &mut String, level: usize) {
+    for _ in 0 .. level {
+        out.push_str("  ");
+    }
+}
+
+// a double-quoted string, escaped the same way for JSON and S-expressions
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"'  => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c    => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+fn string(s: &str) -> Dump {
+    Dump::Str(s.to_string())
+}
+
+fn list<T, F: Fn(&T) -> Dump>(items: &[T], f: F) -> Dump {
+    Dump::List(items.iter().map(f).collect())
+}
+
+fn suffix(t: Option<NumberType>) -> Dump {
+    t.map_or(Dump::Null, |t| string(&t.to_string()))
+}
+
+// literals are never negative, so the bits always read back as a magnitude, e.g. a negated
+// `9223372036854775808` or a `u64` above `i64::MAX`
+fn integer(n: i64) -> Dump {
+    Dump::Number((n as u64).to_string())
+}
+
+// what one file dumps to, under its name, so the dumps of several files stay apart
+pub fn file(name: &str, field: &'static str, dumped: Dump) -> Dump {
+    Dump::Node("file", None, vec![("name", string(name)), (field, dumped)])
+}
+
+pub fn tokens(tokens: &[Spanned<Token>]) -> Dump {
+    list(tokens, token)
+}
+
+// literals by their kind, keywords and symbols by how they're written
+pub fn token(token: &Spanned<Token>) -> Dump {
+    let (kind, fields) = match token.node {
+        Token::Identifier(ref name) => ("identifier", vec![("name", string(name))]),
+        Token::Integer(n, t)        => ("integer", vec![("value", integer(n)), ("suffix", suffix(t))]),
+        Token::Float(n, t)          => ("float", vec![("value", Dump::Number(format!("{:?}", n))), ("suffix", suffix(t))]),
+        Token::Text(ref text)       => ("text", vec![("value", string(text))]),
+        Token::DocComment(ref text) => ("doc_comment", vec![("value", string(text))]),
+
+        Token::Interpolation(ref parts) => ("interpolation", vec![("parts", list(parts, |part| match *part {
+            TextPart::Literal(ref text) => string(text),
+            TextPart::Code(ref code, _) => tokens(code),
+        }))]),
+
+        ref other => {
+            let text = other.to_string().trim_matches('`').to_string();
+            let kind = if text.chars().all(char::is_alphabetic) { "keyword" } else { "symbol" };
+
+            (kind, vec![("text", Dump::Str(text))])
+        }
+    };
+
+    Dump::Node(kind, Some(token.span), fields)
+}
+
+pub fn program(program: &Program) -> Dump {
+    Dump::Node("program", None, vec![("items", list(&program.items, item))])
+}
+
+pub fn item(item: &Spanned<Item>) -> Dump {
+    match item.node {
+        Item::Function(ref f)  => function(f, item.span),
+        Item::Statement(ref s) => statement(s, Some(item.span)),
+    }
+}
+
+pub fn function(function: &Function, span: Span) -> Dump {
+    Dump::Node("function", Some(span), vec![
+        ("name",   string(&function.name)),
+        ("doc",    function.doc.as_ref().map_or(Dump::Null, |d| string(d))),
+        ("params", list(&function.params, param)),
+        ("ret",    string(&function.ret.to_string())),
+        ("body",   statement(&function.body, None)),
+    ])
+}
+
+fn param(param: &Param) -> Dump {
+    Dump::Node("param", None, vec![("name", string(&param.name)), ("type", string(&param.ty.to_string()))])
+}
+
+fn lambda(lambda: &Lambda, span: Span) -> Dump {
+    Dump::Node("lambda", Some(span), vec![
+        ("params",   list(&lambda.params, param)),
+        ("ret",      string(&lambda.ret.to_string())),
+        ("captures", list(&lambda.captures, |c| string(c))),
+        ("body",     statement(&lambda.body, None)),
+    ])
+}
+
+// statements only have a span inside blocks
+pub fn statement(statement: &Statement, span: Option<Span>) -> Dump {
+    let (kind, fields) = match *statement {
+        Statement::If(ref c, ref body)                    => ("if", vec![("condition", expr(c)), ("then", self::statement(body, None))]),
+        Statement::Unless(ref c, ref body)                => ("unless", vec![("condition", expr(c)), ("then", self::statement(body, None))]),
+        Statement::IfElse(ref c, ref body, ref otherwise) => ("if_else", vec![
+            ("condition", expr(c)),
+            ("then",      self::statement(body, None)),
+            ("else",      self::statement(otherwise, None)),
+        ]),
+
+        Statement::UnlessElse(ref c, ref body, ref otherwise) => ("unless_else", vec![
+            ("condition", expr(c)),
+            ("then",      self::statement(body, None)),
+            ("else",      self::statement(otherwise, None)),
+        ]),
+
+        Statement::While(ref c, ref body) => ("while", vec![("condition", expr(c)), ("body", self::statement(body, None))]),
+        Statement::Until(ref c, ref body) => ("until", vec![("condition", expr(c)), ("body", self::statement(body, None))]),
+
+        Statement::For(ref name, ref iterable, ref body) => ("for", vec![
+            ("variable", string(name)),
+            ("iterable", expr(iterable)),
+            ("body",     self::statement(body, None)),
+        ]),
+
+        Statement::Var(ref name, ref ty, ref value) => ("var", vec![
+            ("name",  string(name)),
+            ("type",  string(&ty.to_string())),
+            ("value", value.as_ref().map_or(Dump::Null, |v| expr(v))),
+        ]),
+
+        Statement::Block(ref statements) => ("block", vec![
+            ("statements", list(statements, |s| self::statement(&s.node, Some(s.span)))),
+        ]),
+
+        Statement::Expr(ref e)          => ("expr", vec![("expr", expr(e))]),
+        Statement::ReturnWithVal(ref e) => ("return", vec![("value", expr(e))]),
+        Statement::Return               => ("return", vec![("value", Dump::Null)]),
+        Statement::Break                => ("break", vec![]),
+        Statement::Continue             => ("continue", vec![]),
+    };
+
+    Dump::Node(kind, span, fields)
+}
+
+pub fn expr(expr: &Spanned<Expr>) -> Dump {
+    let (kind, fields) = match expr.node {
+        Expr::Integer(n, t)    => ("integer", vec![("value", integer(n)), ("suffix", suffix(t))]),
+        Expr::Float(n, t)      => ("float", vec![("value", Dump::Number(format!("{:?}", n))), ("suffix", suffix(t))]),
+        Expr::Text(ref text)   => ("text", vec![("value", string(text))]),
+        Expr::Identifier(ref name) => ("identifier", vec![("name", string(name))]),
+        Expr::True             => ("bool", vec![("value", Dump::Bool(true))]),
+        Expr::False            => ("bool", vec![("value", Dump::Bool(false))]),
+
+        Expr::Interpolation(ref parts) => ("interpolation", vec![("parts", list(parts, |part| match *part {
+            InterpolationPart::Literal(ref text) => string(text),
+            InterpolationPart::Expr(ref e)       => self::expr(e),
+        }))]),
+
+        Expr::FnCall(ref name, ref args) => ("call", vec![("name", string(name)), ("args", list(args, self::expr))]),
+
+        Expr::Dot(ref object, ref member)  => ("dot", vec![("object", self::expr(object)), ("member", self::expr(member))]),
+        Expr::Index(ref object, ref index) => ("index", vec![("object", self::expr(object)), ("index", self::expr(index))]),
+
+        Expr::Assignment(ref target, ref value) => ("assign", vec![("target", self::expr(target)), ("value", self::expr(value))]),
+
+        Expr::CompoundAssignment(op, ref target, ref value) => ("compound_assign", vec![
+            ("op",     string(&op.to_string())),
+            ("target", self::expr(target)),
+            ("value",  self::expr(value)),
+        ]),
+
+        Expr::Binary(ref lhs, op, ref rhs) => ("binary", vec![
+            ("op",  string(&op.to_string())),
+            ("lhs", self::expr(lhs)),
+            ("rhs", self::expr(rhs)),
+        ]),
+
+        Expr::Unary(op, ref operand) => ("unary", vec![("op", string(&op.to_string())), ("operand", self::expr(operand))]),
+        Expr::Range(ref start, ref end) => ("range", vec![("start", self::expr(start)), ("end", self::expr(end))]),
+
+        Expr::Lambda(ref l)      => return lambda(l, expr.span),
+        Expr::Conditional(ref c) => ("conditional", vec![("statement", statement(c, None))]),
+    };
+
+    Dump::Node(kind, Some(expr.span), fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parser::lexer::Lexer;
+    use parser::parser::parse_program;
+
+    const FUNCTION: &str = "func f (a: int) -> str\n    return \"a\\t{a}\"\nend\n";
+
+    fn tokens_of(files: &[(&str, &str)]) -> Dump {
+        Dump::List(files.iter().map(|&(name, source)| file(name, "tokens", tokens(&Lexer::new(source).tokenize().0))).collect())
+    }
+
+    fn program_of(name: &str, source: &str) -> Dump {
+        let (parsed, errors) = parse_program(source);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        Dump::List(vec![file(name, "program", program(&parsed))])
+    }
+
+    #[test]
+    fn tokens_as_json() {
+        assert_eq!(tokens_of(&[("t.ice", "x = -0x1F_u8 # n\n")]).to_json(), r#"[
+  {
+    "kind": "file",
+    "name": "t.ice",
+    "tokens": [
+      {
+        "kind": "identifier",
+        "span": { "start": 0, "end": 1, "line": 1, "column": 1 },
+        "name": "x"
+      },
+      {
+        "kind": "symbol",
+        "span": { "start": 2, "end": 3, "line": 1, "column": 3 },
+        "text": "="
+      },
+      {
+        "kind": "symbol",
+        "span": { "start": 4, "end": 5, "line": 1, "column": 5 },
+        "text": "-"
+      },
+      {
+        "kind": "integer",
+        "span": { "start": 5, "end": 12, "line": 1, "column": 6 },
+        "value": 31,
+        "suffix": "u8"
+      }
+    ]
+  }
+]
+"#);
+    }
+
+    #[test]
+    fn tokens_of_several_files_as_sexp() {
+        assert_eq!(tokens_of(&[("t.ice", "x = -0x1F_u8 # n\n"), ("u.ice", "y\n")]).to_sexp(), r#"((file :name "t.ice"
+    :tokens ((identifier :span (0 1 1 1) :name "x")
+      (symbol :span (2 3 1 3) :text "=")
+      (symbol :span (4 5 1 5) :text "-")
+      (integer :span (5 12 1 6) :value 31 :suffix "u8")))
+  (file :name "u.ice"
+    :tokens ((identifier :span (0 1 1 1) :name "y"))))
+"#);
+    }
+
+    #[test]
+    fn program_as_json() {
+        assert_eq!(program_of("d.ice", FUNCTION).to_json(), r#"[
+  {
+    "kind": "file",
+    "name": "d.ice",
+    "program": {
+      "kind": "program",
+      "items": [
+        {
+          "kind": "function",
+          "span": { "start": 0, "end": 46, "line": 1, "column": 1 },
+          "name": "f",
+          "doc": null,
+          "params": [
+            {
+              "kind": "param",
+              "name": "a",
+              "type": "int"
+            }
+          ],
+          "ret": "str",
+          "body": {
+            "kind": "block",
+            "statements": [
+              {
+                "kind": "return",
+                "span": { "start": 27, "end": 42, "line": 2, "column": 5 },
+                "value": {
+                  "kind": "interpolation",
+                  "span": { "start": 34, "end": 42, "line": 2, "column": 12 },
+                  "parts": [
+                    "a\t",
+                    {
+                      "kind": "identifier",
+                      "span": { "start": 39, "end": 40, "line": 2, "column": 17 },
+                      "name": "a"
+                    }
+                  ]
+                }
+              }
+            ]
+          }
+        }
+      ]
+    }
+  }
+]
+"#);
+    }
+
+    #[test]
+    fn program_as_sexp() {
+        assert_eq!(program_of("d.ice", FUNCTION).to_sexp(), r#"((file :name "d.ice"
+    :program (program
+      :items ((function :span (0 46 1 1) :name "f" :doc nil
+          :params ((param :name "a" :type "int")) :ret "str"
+          :body (block
+            :statements ((return :span (27 42 2 5)
+                :value (interpolation :span (34 42 2 12)
+                  :parts ("a\t"
+                    (identifier :span (39 40 2 17) :name "a")))))))))))
+"#);
+    }
+}
@@ -0,0 +1,471 @@
+use parser::lexer::{Token, Lexer, Associativity};
+use parser::parser::{Program, Item, Function, Param, Statement, Expr, parse_program};
+use parser::error::ParserError;
+use parser::span::{Span, Spanned, LineMap};
+
+use std::usize;
+
+// pretty-prints a source canonically: 4-space indentation, one space around binary operators,
+// as few parentheses as it takes, at most one blank line in a row. comments are kept where they were,
+// relative to the code around them. sources that don't parse are left alone
+pub fn format(source: &str) -> Result<String, Vec<Spanned<ParserError>>> {
+    let (program, errors) = parse_program(source);
+
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+
+    let mut lexer     = Lexer::new(source);
+    let (tokens, _)   = lexer.tokenize();
+
+    let mut formatter = Formatter {
+        source:   source,
+        lines:    LineMap::new(source),
+        comments: lexer.comments().to_vec(),
+        next:     0,
+        elses:    tokens.iter().filter(|t| t.node == Token::Else).map(|t| t.span).collect(),
+        out:      String::new(),
+        level:    0,
+        line:     0,
+        last:     0,
+        fresh:    true,
+    };
+
+    formatter.program(&program);
+
+    Ok(formatter.out)
+}
+
+struct Formatter<'a> {
+    source:   &'a str,
+    lines:    LineMap<'a>,
+    comments: Vec<Spanned<String>>,
+    // the first comment not written yet
+    next:     usize,
+    // `else`s aren't in the AST, they're needed to tell which block a comment is in
+    elses:    Vec<Span>,
+    out:      String,
+    level:    usize,
+    // the source line of the code on the last line written, 0 if there's none to put a comment after
+    line:     usize,
+    // the source line the last thing written ended on
+    last:     usize,
+    // nothing has been written into the current block yet
+    fresh:    bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn program(&mut self, program: &Program) {
+        for item in &program.items {
+            self.comments_before(item.span.start);
+            self.begin(item.span.line);
+
+            match item.node {
+                Item::Function(ref function)   => self.function(function, item.span),
+                Item::Statement(ref statement) => self.statement(statement, item.span),
+            }
+
+            self.finish(item.span);
+        }
+
+        self.comments_before(usize::MAX);
+    }
+
+    fn function(&mut self, function: &Function, span: Span) {
+        self.out.push_str("func ");
+        self.out.push_str(&function.name);
+        self.signature(&function.params, &function.ret.to_string());
+        self.header(span.line);
+        self.block(&function.body, span.end);
+        self.end();
+    }
+
+    fn signature(&mut self, params: &[Param], ret: &str) {
+        let params: Vec<String> = params.iter().map(|p| format!("{}: {}", p.name, p.ty)).collect();
+
+        self.out.push_str(&format!(" ({})", params.join(", ")));
+
+        if ret != "void" {
+            self.out.push_str(" -> ");
+            self.out.push_str(ret);
+        }
+    }
+
+    // a body, indented, along with the comments in it up to `limit`
+    fn block(&mut self, block: &Statement, limit: usize) {
+        self.level += 1;
+
+        if let Statement::Block(ref statements) = *block {
+            for statement in statements.iter() {
+                self.comments_before(statement.span.start);
+                self.begin(statement.span.line);
+                self.statement(&statement.node, statement.span);
+                self.finish(statement.span);
+            }
+        }
+
+        self.comments_before(limit);
+
+        self.level -= 1;
+    }
+
+    fn statement(&mut self, statement: &Statement, span: Span) {
+        match *statement {
+            Statement::If(..) | Statement::IfElse(..) | Statement::Unless(..) | Statement::UnlessElse(..) => {
+                self.conditional(statement, span.end);
+                self.end();
+            }
+
+            Statement::While(ref condition, ref body) | Statement::Until(ref condition, ref body) => {
+                self.out.push_str(if let Statement::While(..) = *statement { "while " } else { "until " });
+                self.expr(condition, 0);
+                self.header(condition.span.line);
+                self.block(body, span.end);
+                self.end();
+            }
+
+            Statement::For(ref name, ref iterable, ref body) => {
+                self.out.push_str(&format!("for {} in ", name));
+                self.expr(iterable, 0);
+                self.header(iterable.span.line);
+                self.block(body, span.end);
+                self.end();
+            }
+
+            Statement::Var(ref name, ref ty, ref value) => {
+                self.out.push_str(&format!("{}: {}", name, ty));
+
+                if let Some(ref value) = *value {
+                    self.out.push_str(" = ");
+                    self.expr(value, 0);
+                }
+            }
+
+            // only ever the body of something else
+            Statement::Block(_) => self.block(statement, span.end),
+
+            Statement::Expr(ref expr) => self.expr(expr, 0),
+
+            Statement::ReturnWithVal(ref expr) => {
+                self.out.push_str("return ");
+                self.expr(expr, 0);
+            }
+
+            Statement::Return   => self.out.push_str("return"),
+            Statement::Break    => self.out.push_str("break"),
+            Statement::Continue => self.out.push_str("continue"),
+        }
+    }
+
+    // everything of an `if` or `unless` but its `end`, chained conditionals go on the line of their `else`
+    fn conditional(&mut self, statement: &Statement, limit: usize) {
+        let (keyword, condition, body, otherwise) = match *statement {
+            Statement::If(ref c, ref b)                => ("if ", c, b, None),
+            Statement::IfElse(ref c, ref b, ref o)     => ("if ", c, b, Some(o)),
+            Statement::Unless(ref c, ref b)            => ("unless ", c, b, None),
+            Statement::UnlessElse(ref c, ref b, ref o) => ("unless ", c, b, Some(o)),
+            _                                          => return,
+        };
+
+        self.out.push_str(keyword);
+        self.expr(condition, 0);
+        self.header(condition.span.line);
+
+        let otherwise = match otherwise {
+            Some(otherwise) => otherwise,
+            None            => return self.block(body, limit),
+        };
+
+        // the `else` is the first one after everything in the body
+        let after = match **body {
+            Statement::Block(ref statements) => statements.last().map_or(condition.span.end, |s| s.span.end),
+            _                                => condition.span.end,
+        };
+
+        let otherwise_span = self.elses.iter().cloned().find(|s| s.start >= after).unwrap_or(Span::default());
+
+        self.block(body, otherwise_span.start);
+        self.indent();
+        self.out.push_str("else");
+
+        match **otherwise {
+            Statement::If(..) | Statement::IfElse(..) | Statement::Unless(..) | Statement::UnlessElse(..) => {
+                self.out.push(' ');
+                self.conditional(otherwise, limit);
+            }
+
+            _ => {
+                self.header(otherwise_span.line);
+                self.block(otherwise, limit);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>, min_precedence: i32) {
+        let precedence = self.precedence(expr);
+        let parens     = precedence < min_precedence;
+
+        if parens {
+            self.out.push('(');
+        }
+
+        match expr.node {
+            // literals are written as they are in the source, keeping their radix, digit separators,
+            // suffixes and the kind of quotes, escapes and code of strings. the span of a parenthesised
+            // literal takes in the parentheses, which no literal starts or ends with itself
+            Expr::Integer(..) | Expr::Float(..) | Expr::Text(_) | Expr::Interpolation(_) => {
+                let text = self.source[expr.span.start .. expr.span.end].trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace());
+
+                self.out.push_str(text);
+            }
+
+            Expr::Identifier(ref name) => self.out.push_str(name),
+
+            Expr::FnCall(ref name, ref args) if self.juxtaposed(expr) => {
+                self.out.push_str(name);
+
+                for (i, arg) in args.iter().enumerate() {
+                    self.out.push_str(if i > 0 { ", " } else { " " });
+
+                    // a juxtaposed call before the last argument would take the ones after it as its own
+                    let min = if i + 1 < args.len() && self.juxtaposed(arg) { precedence + 1 } else { precedence };
+                    let at  = self.out.len();
+
+                    self.expr(arg, min);
+
+                    // `f -x` would be a subtraction
+                    if i == 0 && self.out[at ..].starts_with('-') {
+                        self.out.insert(at, '(');
+                        self.out.push(')');
+                    }
+                }
+            }
+
+            Expr::FnCall(ref name, ref args) => {
+                self.out.push_str(name);
+                self.out.push('(');
+
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+
+                    self.expr(arg, 0);
+                }
+
+                self.out.push(')');
+            }
+
+            Expr::Dot(ref lhs, ref member) => {
+                self.expr(lhs, precedence);
+                self.out.push('.');
+                self.expr(member, 0);
+            }
+
+            Expr::Index(ref lhs, ref index) => {
+                self.expr(lhs, precedence);
+                self.out.push('[');
+                self.expr(index, 0);
+                self.out.push(']');
+            }
+
+            Expr::Assignment(ref lhs, ref rhs) => self.infix(lhs, " = ", &Token::Assign, rhs),
+
+            Expr::CompoundAssignment(op, ref lhs, ref rhs) => {
+                let symbol = format!(" {}= ", op);
+
+                self.infix(lhs, &symbol, &Token::Assign, rhs)
+            }
+
+            Expr::Binary(ref lhs, op, ref rhs) => self.infix(lhs, &format!(" {} ", op), &op.to_token(), rhs),
+            Expr::Range(ref lhs, ref rhs)      => self.infix(lhs, "..", &Token::DotDot, rhs),
+
+            Expr::Unary(op, ref operand) => {
+                self.out.push_str(&op.to_string());
+                self.expr(operand, precedence);
+            }
+
+            Expr::Lambda(ref lambda) => {
+                self.out.push_str("lambda");
+                self.signature(&lambda.params, &lambda.ret.to_string());
+                self.header(expr.span.line);
+                self.block(&lambda.body, expr.span.end);
+                self.indent();
+                self.out.push_str("end");
+            }
+
+            Expr::Conditional(ref conditional) => {
+                self.conditional(conditional, expr.span.end);
+                self.indent();
+                self.out.push_str("end");
+            }
+
+            Expr::True  => self.out.push_str("true"),
+            Expr::False => self.out.push_str("false"),
+        }
+
+        if parens {
+            self.out.push(')');
+        }
+    }
+
+    fn infix(&mut self, lhs: &Spanned<Expr>, symbol: &str, token: &Token, rhs: &Spanned<Expr>) {
+        let precedence = Lexer::get_precedence(token);
+
+        let (lhs_precedence, rhs_precedence) = match Lexer::get_associativity(token) {
+            Associativity::Left  => (precedence, precedence + 1),
+            Associativity::Right => (precedence + 1, precedence),
+        };
+
+        self.expr(lhs, lhs_precedence);
+        self.out.push_str(symbol);
+        self.expr(rhs, rhs_precedence);
+    }
+
+    // how tightly an expression holds together, those binding looser than their place needs are parenthesised
+    fn precedence(&self, expr: &Spanned<Expr>) -> i32 {
+        match expr.node {
+            // a juxtaposed call takes everything after it as arguments
+            Expr::FnCall(..) if self.juxtaposed(expr) => Lexer::get_precedence(&Token::Assign) + 1,
+
+            Expr::Assignment(..)         |
+            Expr::CompoundAssignment(..) => Lexer::get_precedence(&Token::Assign),
+            Expr::Range(..)              => Lexer::get_precedence(&Token::DotDot),
+            Expr::Binary(_, op, _)       => Lexer::get_precedence(&op.to_token()),
+            Expr::Unary(op, _)           => Lexer::get_prefix_precedence(&op.to_token()),
+            Expr::Dot(..)                => Lexer::get_precedence(&Token::Period),
+            Expr::Index(..)              => Lexer::get_precedence(&Token::LBracket),
+            _                            => i32::max_value(),
+        }
+    }
+
+    // whether a call was written as `f x, y` rather than `f(x, y)`, which the AST doesn't tell.
+    // the span of a parenthesised call starts at the parenthesis
+    fn juxtaposed(&self, call: &Spanned<Expr>) -> bool {
+        match call.node {
+            Expr::FnCall(ref name, ref args) if !args.is_empty() => {
+                let rest = self.source[call.span.start ..].trim_start_matches(|c: char| c == '(' || c.is_whitespace());
+
+                !rest[name.len() ..].starts_with('(')
+            }
+
+            _ => false,
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0 .. self.level {
+            self.out.push_str("    ");
+        }
+    }
+
+    // starts a line for something from source line `line`, keeping a blank line before it if there was one
+    fn begin(&mut self, line: usize) {
+        if !self.fresh && self.last > 0 && line > self.last + 1 {
+            self.out.push('\n');
+        }
+
+        self.fresh = false;
+        self.indent();
+    }
+
+    // ends the first line of something holding a block
+    fn header(&mut self, line: usize) {
+        self.out.push('\n');
+
+        self.line  = line;
+        self.last  = line;
+        self.fresh = true;
+    }
+
+    fn end(&mut self) {
+        self.indent();
+        self.out.push_str("end");
+    }
+
+    // ends the line of a statement or item spanning `span`
+    fn finish(&mut self, span: Span) {
+        self.out.push('\n');
+
+        self.line = self.lines.line_of(span.end.saturating_sub(1));
+        self.last = self.line;
+    }
+
+    fn comments_before(&mut self, offset: usize) {
+        while let Some(comment) = self.comments.get(self.next).cloned() {
+            if comment.span.start >= offset {
+                break
+            }
+
+            self.next += 1;
+
+            // after code on the same line
+            if comment.span.line == self.line {
+                self.out.pop();
+                self.out.push(' ');
+            } else {
+                self.begin(comment.span.line);
+            }
+
+            self.out.push_str(&comment.node);
+            self.out.push('\n');
+
+            self.line = 0;
+            self.last = self.lines.line_of(comment.span.end.saturating_sub(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dump;
+
+    const CORPUS: &[&str] = &[
+        include_str!("../examples/fib.ice"),
+
+        "## adds up\n## its arguments\nfunc add (a: int, b: int) -> int\n    return a + b # the sum\nend\n\n\n\nx: int = add 1, 2\n",
+
+        "func f (n: i64) -> i64\n  if n < 0\n      return -n\n  else if n == 0\n    return 0x0_F\n  else\n    return (n * (n - 1)) % 1_000\n  end\nend\n",
+
+        "i: int = 0\nuntil i >= 10\n    i += 1\n    if i % 2 == 0\n        continue\n    end\nend\nfor j in 0..3\n    print(j)\nend\n",
+
+        "n: int = 1\ns: str = \"n is {n + 1}\"\nr: str = r'raw {n} \\n'\nt: str = '''two\n  lines'''\nu: f32 = (1.5e3f32)\nv: int = ((1)) + (2)\n",
+
+        "twice: func (int) -> int = lambda (x: int) -> int\n    return x * 2\nend\nsign: int = if twice(1) < 0\n    -1\nelse\n    1\nend\nb: bool = ~(1 < 2) && sign ~= 0 || false\n",
+    ];
+
+    fn shape(source: &str) -> String {
+        let (program, errors) = parse_program(source);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        dump::program(&program).without_spans().to_sexp()
+    }
+
+    #[test]
+    fn formatting_is_stable() {
+        for source in CORPUS {
+            let once  = format(source).unwrap();
+            let twice = format(&once).unwrap();
+
+            assert_eq!(once, twice, "formatting\n{}", source);
+        }
+    }
+
+    #[test]
+    fn formatting_keeps_the_tree() {
+        for source in CORPUS {
+            assert_eq!(shape(&format(source).unwrap()), shape(source), "formatting\n{}", source);
+        }
+    }
+
+    #[test]
+    fn literals_keep_their_spelling_but_not_their_parentheses() {
+        assert_eq!(
+            format("x: int = (0o755) + ((1_000))\ns: str = (r'a\\n')\n").unwrap(),
+            "x: int = 0o755 + 1_000\ns: str = r'a\\n'\n",
+        );
+    }
+}
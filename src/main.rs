@@ -1,69 +1,402 @@
 mod parser;
 mod vm;
+mod codegen;
+mod format;
+mod dump;
 
 use parser::lexer::Lexer;
+use parser::span::{Spanned, LineMap};
+use parser::parser::{Program, parse_program};
+use parser::error::ParserError;
+use parser::diagnostic::Diagnostic;
+
+use codegen::codegen::Generator;
 
 use vm::module::Module;
 use vm::context::Context;
-use vm::builder::{
-    Builder,
-    BasicBlock
-};
-
-use vm::llvm_type::{
-    VMRepresentation,
-    int8_type,
-    void_type,
-    double_type,
-};
-use vm::function::Function;
-
-fn lexer_debug() {
-    let input = "
-func foo(x: int, y: int) -> int
-    return x + y && true
-end
-
-foo = 133.7 + \"I'm a string, hello\"
+use vm::target::{TargetMachine, FileType};
+use vm::engine::Engine;
+
+use dump::Dump;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{self, Command};
+
+const USAGE: &'static str = "\
+usage: icecream <command> [options] [files...]
+
+commands:
+    run <files>                 compile the program and run its `main`
+    build <files> [-o <out>]    compile the program into an executable
+    check <files>               report errors without producing anything
+    emit <what> <files> [-o <out>]
+        --tokens, --ast         the tokens or syntax tree of each file, as JSON or with --sexp as S-expressions
+        --llvm-ir, --asm        the program as LLVM IR or native assembly
+        --obj                   the program as a native object file
+    fmt [--check] <files>       format files in place, or only list the ones that aren't formatted
+
+files are read from stdin when none are given, or when one is `-`
 ";
 
-    println!("{}", input);
+struct Options {
+    files:  Vec<String>,
+    output: Option<String>,
+    flags:  Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            files:  Vec::new(),
+            output: None,
+            flags:  Vec::new(),
+        };
+
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "-o" {
+                match args.next() {
+                    Some(output) => options.output = Some(output.clone()),
+                    None         => return Err("`-o` needs a file name after it".to_string()),
+                }
+            } else if arg.starts_with("--") {
+                options.flags.push(arg.clone());
+            } else {
+                options.files.push(arg.clone());
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+
+    // fails on the first flag that isn't one of `allowed`
+    fn allow(&self, allowed: &[&str]) -> Result<(), i32> {
+        match self.flags.iter().find(|f| !allowed.contains(&f.as_str())) {
+            Some(flag) => Err(usage_error(&format!("unknown option `{}`", flag))),
+            None       => Ok(()),
+        }
+    }
+}
+
+// an input file, or stdin when it's named `-`
+struct Source {
+    name: String,
+    text: String,
+}
+
+impl Source {
+    // the file name without its extension, to name outputs after
+    fn stem(&self) -> String {
+        match self.name.as_str() {
+            "-" => "out".to_string(),
+            _   => Path::new(&self.name).file_stem().map_or("out".to_string(), |s| s.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+fn error(message: &str) -> i32 {
+    eprintln!("error: {}", message);
+    1
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    2
+}
+
+fn read_sources(files: &[String]) -> Result<Vec<Source>, i32> {
+    let stdin = vec!["-".to_string()];
+    let files = if files.is_empty() { &stdin[..] } else { files };
+
+    let mut sources = Vec::new();
 
-    let mut lexer = Lexer::new(input);
+    for name in files {
+        let mut text = String::new();
 
-    while let Some(t) = lexer.next_token() {
-        println!("found: {:?}", t);
+        let result = if name == "-" {
+            io::stdin().read_to_string(&mut text).map(|_| ())
+        } else {
+            fs::File::open(name).and_then(|mut file| file.read_to_string(&mut text)).map(|_| ())
+        };
+
+        if let Err(e) = result {
+            return Err(error(&format!("{}: {}", name, e)))
+        }
+
+        sources.push(Source {
+            name: name.clone(),
+            text: text,
+        });
+    }
+
+    Ok(sources)
+}
+
+fn report<E>(source: &Source, errors: &[Spanned<E>]) where for<'a> Diagnostic: From<&'a Spanned<E>> {
+    let lines = LineMap::new(&source.text);
+
+    for error in errors {
+        eprint!("{}", Diagnostic::from(error).render(&source.name, &lines));
     }
+}
+
+// parses every source, reporting all of their errors before giving up
+fn parse(sources: &[Source]) -> Result<Vec<Program>, i32> {
+    let mut programs = Vec::new();
+    let mut failed   = 0;
+
+    for source in sources {
+        let (program, errors) = parse_program(&source.text);
+
+        report(source, &errors);
+
+        failed += errors.len();
+        programs.push(program);
+    }
+
+    abort(failed)?;
 
-    println!("\n");
+    Ok(programs)
 }
 
-fn function_debug() {
-    let mut context = Context::new();
-    let mut module = Module::new("strawberry", &context);
-    let mut builder = Builder::new(&mut context);
+fn abort(failed: usize) -> Result<(), i32> {
+    match failed {
+        0 => Ok(()),
+        1 => Err(error("aborting due to the previous error")),
+        n => Err(error(&format!("aborting due to {} previous errors", n))),
+    }
+}
+
+// compiles the sources into one module, named after the first
+fn compile(context: &Context, sources: &[Source]) -> Result<Module, i32> {
+    let programs      = parse(sources)?;
+    let mut generator = Generator::new(context, &sources[0].stem());
+    let mut failed    = 0;
+
+    // everything is declared first, so calls can reach functions in any file
+    for (source, program) in sources.iter().zip(&programs) {
+        let errors = generator.declare(program);
+
+        report(source, &errors);
+        failed += errors.len();
+    }
+
+    for (source, program) in sources.iter().zip(&programs) {
+        let errors = generator.define(program);
 
-    let function = Function::new(
-        &module, "foo", &mut [
-            int8_type(&context)
-        ], double_type(&context),
-    );
+        report(source, &errors);
+        failed += errors.len();
+    }
+
+    abort(failed)?;
+
+    generator.finish().map_err(|e| error(&format!("generated an invalid module: {}", e)))
+}
 
-    let block: BasicBlock = function.new_basic_block("entry");
-    builder.move_to_end(block);
+fn write_output(output: Option<&str>, bytes: &[u8]) -> i32 {
+    let result = match output {
+        None | Some("-") => io::stdout().write_all(bytes),
+        Some(path)       => fs::write(path, bytes),
+    };
+
+    match result {
+        Ok(())  => 0,
+        Err(e)  => error(&format!("{}: {}", output.unwrap_or("stdout"), e)),
+    }
+}
 
-    let summation = builder.add(
-        13u8.to_representation(&context),
-        37u8.to_representation(&context),
-        "summation",
-    );
+fn check(options: &Options) -> Result<i32, i32> {
+    options.allow(&[])?;
 
-    builder.return_value(summation);
+    compile(&Context::new(), &read_sources(&options.files)?)?;
 
-    println!("{}", module);
+    Ok(0)
+}
+
+fn run(options: &Options) -> Result<i32, i32> {
+    options.allow(&[])?;
+
+    let sources = read_sources(&options.files)?;
+    let context = Context::new();
+    let module  = compile(&context, &sources)?;
+
+    let engine  = Engine::new(module).map_err(|e| error(&e))?;
+
+    engine.run("main").map_err(|e| error(&e))
+}
+
+fn build(options: &Options) -> Result<i32, i32> {
+    options.allow(&[])?;
+
+    let sources = read_sources(&options.files)?;
+    let output  = options.output.clone().unwrap_or(sources[0].stem());
+
+    let context = Context::new();
+    let module  = compile(&context, &sources)?;
+    let machine = TargetMachine::native().map_err(|e| error(&e))?;
+    let object  = machine.emit(&module, FileType::Object).map_err(|e| error(&e))?;
+
+    // linked by the system's C compiler, which knows where the C runtime is
+    let object_path = format!("{}.o", output);
+
+    if write_output(Some(&object_path), &object) != 0 {
+        return Err(1)
+    }
+
+    let status = Command::new("cc").arg(&object_path).arg("-o").arg(&output).status();
+
+    let _ = fs::remove_file(&object_path);
+
+    match status {
+        Ok(ref status) if status.success() => Ok(0),
+        Ok(_)                              => Err(error("linking failed")),
+        Err(e)                             => Err(error(&format!("couldn't run `cc`: {}", e))),
+    }
+}
+
+fn emit(options: &Options) -> Result<i32, i32> {
+    let kinds = ["--tokens", "--ast", "--llvm-ir", "--asm", "--obj"];
+
+    options.allow(&["--tokens", "--ast", "--llvm-ir", "--asm", "--obj", "--sexp", "--json"])?;
+
+    let kind = match options.flags.iter().filter(|f| kinds.contains(&f.as_str())).collect::<Vec<_>>().as_slice() {
+        [kind] => kind.as_str(),
+        _      => return Err(usage_error("`emit` needs exactly one of --tokens, --ast, --llvm-ir, --asm or --obj")),
+    };
+
+    let sources = read_sources(&options.files)?;
+    let output  = options.output.as_ref().map(|o| o.as_str());
+
+    // the tokens and trees are dumped even when there are errors, as far as they go
+    if kind == "--tokens" || kind == "--ast" {
+        let mut files  = Vec::new();
+        let mut failed = 0;
+
+        for source in &sources {
+            let dumped = if kind == "--tokens" {
+                let (tokens, errors) = Lexer::new(&source.text).tokenize();
+
+                failed += errors.len();
+
+                report(source, &errors.into_iter().map(|e| Spanned::new(ParserError::Lex(e.node), e.span)).collect::<Vec<_>>());
+
+                dump::file(&source.name, "tokens", dump::tokens(&tokens))
+            } else {
+                let (program, errors) = parse_program(&source.text);
+
+                failed += errors.len();
+
+                report(source, &errors);
+
+                dump::file(&source.name, "program", dump::program(&program))
+            };
+
+            files.push(dumped);
+        }
+
+        // one document for all the files, so it stays valid JSON however many there are
+        let files = Dump::List(files);
+        let out   = if options.has("--sexp") { files.to_sexp() } else { files.to_json() };
+
+        let status = write_output(output, out.as_bytes());
+
+        return Ok(if failed > 0 { 1 } else { status })
+    }
+
+    let context = Context::new();
+    let module  = compile(&context, &sources)?;
+
+    let bytes = match kind {
+        "--llvm-ir" => module.to_string().into_bytes(),
+
+        _ => {
+            let machine = TargetMachine::native().map_err(|e| error(&e))?;
+
+            if kind == "--asm" {
+                machine.emit(&module, FileType::Assembly).map_err(|e| error(&e))?
+            } else {
+                machine.emit(&module, FileType::Object).map_err(|e| error(&e))?
+            }
+        }
+    };
+
+    // object files don't belong on a terminal
+    if kind == "--obj" && output.is_none() {
+        return Ok(write_output(Some(&format!("{}.o", sources[0].stem())), &bytes))
+    }
+
+    Ok(write_output(output, &bytes))
+}
+
+// formats files in place, or with `--check` only lists those that aren't formatted. stdin is formatted to stdout
+fn fmt(options: &Options) -> Result<i32, i32> {
+    options.allow(&["--check"])?;
+
+    let check      = options.has("--check");
+    let mut status = 0;
+
+    for source in read_sources(&options.files)? {
+        let formatted = match format::format(&source.text) {
+            Ok(formatted) => formatted,
+            Err(errors)   => {
+                report(&source, &errors);
+                status = 1;
+                continue
+            }
+        };
+
+        if check {
+            if formatted != source.text {
+                println!("{} is not formatted", source.name);
+                status = 1;
+            }
+        } else if source.name == "-" {
+            status |= write_output(None, formatted.as_bytes());
+        } else if formatted != source.text {
+            status |= write_output(Some(&source.name), formatted.as_bytes());
+        }
+    }
+
+    Ok(status)
 }
 
 fn main() {
-    lexer_debug();
-    function_debug();
-}
\ No newline at end of file
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None                  => process::exit(usage_error("no command given")),
+    };
+
+    let options = match Options::parse(rest) {
+        Ok(options) => options,
+        Err(e)      => process::exit(usage_error(&e)),
+    };
+
+    let result = match command {
+        "run"   => run(&options),
+        "build" => build(&options),
+        "check" => check(&options),
+        "emit"  => emit(&options),
+        "fmt"   => fmt(&options),
+
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            Ok(0)
+        }
+
+        _ => Err(usage_error(&format!("unknown command `{}`", command))),
+    };
+
+    process::exit(match result {
+        Ok(status) | Err(status) => status,
+    })
+}
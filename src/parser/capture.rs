@@ -0,0 +1,85 @@
+use parser::parser::{Statement, Expr, Lambda, Param};
+use parser::visitor::{Visitor, walk_statement, walk_expr};
+use parser::span::Spanned;
+
+// the names a lambda body uses without binding them itself, in order of first use.
+// these may also name functions or globals, code generation keeps only the ones that
+// resolve to locals of the enclosing function
+pub fn captures(params: &[Param], body: &Statement) -> Vec<String> {
+    let mut scope = Scope {
+        bound: params.iter().map(|p| p.name.clone()).collect(),
+        free:  Vec::new(),
+    };
+
+    scope.visit_statement(body);
+
+    scope.free
+}
+
+struct Scope {
+    bound: Vec<String>,
+    free:  Vec<String>,
+}
+
+impl Scope {
+    fn use_name(&mut self, name: &str) {
+        if !self.bound.iter().any(|n| n == name) && !self.free.iter().any(|n| n == name) {
+            self.free.push(name.to_string())
+        }
+    }
+}
+
+impl Visitor for Scope {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match *statement {
+            // bound from the declaration on, not before
+            Statement::Var(ref name, ..) => {
+                walk_statement(self, statement);
+
+                self.bound.push(name.clone());
+            }
+
+            Statement::Block(_) => {
+                let depth = self.bound.len();
+
+                walk_statement(self, statement);
+
+                self.bound.truncate(depth);
+            }
+
+            Statement::For(ref name, ref iterable, ref body) => {
+                self.visit_expr(iterable);
+
+                self.bound.push(name.clone());
+                self.visit_statement(body);
+                self.bound.pop();
+            }
+
+            _ => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        match expr.node {
+            Expr::Identifier(ref name) => self.use_name(name),
+
+            Expr::FnCall(ref name, _) => {
+                self.use_name(name);
+
+                walk_expr(self, expr);
+            }
+
+            // the member isn't a variable
+            Expr::Dot(ref lhs, _) => self.visit_expr(lhs),
+
+            _ => walk_expr(self, expr),
+        }
+    }
+
+    // whatever a nested lambda captures that isn't bound here is captured from further out
+    fn visit_lambda(&mut self, lambda: &Lambda) {
+        for name in &lambda.captures {
+            self.use_name(name);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use parser::span::{Span, Spanned, LineMap};
+use parser::error::{ParserError, LexError};
+
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub label:   String,
+    pub span:    Span,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, label: String, span: Span) -> Diagnostic {
+        Diagnostic {
+            message: message,
+            label:   label,
+            span:    span,
+        }
+    }
+
+    // renders the message, its location and the offending line with the span underlined:
+    //
+    //   error: Expected `)`, found `->`
+    //    --> fib.ice:1:21
+    //     |
+    //   1 | func broken (a: int -> int
+    //     |                     ^^ expected `)`
+    pub fn render(&self, file: &str, lines: &LineMap) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "error: {}", self.message);
+        let _ = writeln!(out, " --> {}:{}", file, self.span);
+
+        let source = match lines.line(self.span.line) {
+            Some(source) => source,
+            None         => return out,
+        };
+
+        let number = self.span.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        // carets stop at the end of the line for spans reaching past it
+        let offset = source.chars().take(self.span.column - 1).map(|c| c.len_utf8()).sum::<usize>();
+        let width  = source[offset ..].char_indices()
+                                      .take_while(|&(i, _)| i < self.span.len())
+                                      .count()
+                                      .max(1);
+
+        let indent: String = source.chars()
+                                   .take(self.span.column - 1)
+                                   .map(|c| if c == '\t' { '\t' } else { ' ' })
+                                   .collect();
+
+        let _ = writeln!(out, "{} |", gutter);
+        let _ = writeln!(out, "{} | {}", number, source);
+        let _ = writeln!(out, "{} | {}{} {}", gutter, indent, "^".repeat(width), self.label);
+
+        out
+    }
+}
+
+impl<'a> From<&'a Spanned<ParserError>> for Diagnostic {
+    fn from(error: &'a Spanned<ParserError>) -> Diagnostic {
+        Diagnostic::new(error.node.to_string(), error.node.label(), error.span)
+    }
+}
+
+impl<'a> From<&'a Spanned<LexError>> for Diagnostic {
+    fn from(error: &'a Spanned<LexError>) -> Diagnostic {
+        Diagnostic::new(error.node.to_string(), "here".to_string(), error.span)
+    }
+}
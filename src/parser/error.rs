@@ -1,35 +1,97 @@
+use parser::lexer::Token;
+
 use std::error::Error;
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParserError {
-    BadInput,
-    UnknownOperator,
-    MissingRParen,
-    FnMissingName,
-    FnMissingParameters,
-    OutOfInput,
+    Lex(LexError),
+    Expected { expected: String, found: Token },
+    UnknownOperator(Token),
+    MissingRParen(Option<Token>),
+    FnMissingName(Option<Token>),
+    FnMissingParameters(Option<Token>),
+    InvalidAssignment,
+    OutsideLoop(Token),
+    OutOfInput { expected: String },
+}
+
+impl ParserError {
+    // a short note to put under the offending source
+    pub fn label(&self) -> String {
+        match *self {
+            ParserError::Lex(_)                          => "here".to_string(),
+            ParserError::Expected { ref expected, .. }   => format!("expected {}", expected),
+            ParserError::UnknownOperator(_)              => "expected an expression".to_string(),
+            ParserError::MissingRParen(_)                => "expected `)`".to_string(),
+            ParserError::FnMissingName(_)                => "expected a function name".to_string(),
+            ParserError::FnMissingParameters(_)          => "expected `(`".to_string(),
+            ParserError::InvalidAssignment               => "cannot be assigned to".to_string(),
+            ParserError::OutsideLoop(_)                  => "not inside a loop".to_string(),
+            ParserError::OutOfInput { ref expected }     => format!("expected {}", expected),
+        }
+    }
+}
+
+fn found(token: &Option<Token>) -> String {
+    match *token {
+        Some(ref token) => token.to_string(),
+        None            => "end of input".to_string(),
+    }
 }
 
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+        match *self {
+            ParserError::Lex(ref e)                             => write!(f, "{}", e),
+            ParserError::Expected { ref expected, ref found }   => write!(f, "Expected {}, found {}", expected, found),
+            ParserError::UnknownOperator(ref t)                 => write!(f, "Unexpected operator {}", t),
+            ParserError::MissingRParen(ref t)                   => write!(f, "Expected `)`, found {}", found(t)),
+            ParserError::FnMissingName(ref t)                   => write!(f, "Function declaration is missing a name, found {}", found(t)),
+            ParserError::FnMissingParameters(ref t)             => write!(f, "Function declaration is missing parameters, found {}", found(t)),
+            ParserError::InvalidAssignment                      => write!(f, "Invalid assignment target"),
+            ParserError::OutsideLoop(ref t)                     => write!(f, "{} outside of a loop", t),
+            ParserError::OutOfInput { ref expected }            => write!(f, "Unexpected end of input, expected {}", expected),
+        }
     }
 }
 
 impl Error for ParserError {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match *self {
-            ParserError::BadInput            => "Illegal characters found in input stream",
-            ParserError::UnknownOperator     => "Unknown operator",
-            ParserError::MissingRParen       => "Expected ')",
-            ParserError::FnMissingName       => "Function declaration is missing name",
-            ParserError::FnMissingParameters => "Function declaration is missing parameters",
-            ParserError::OutOfInput           => "Parser fucked up reading of input stream"
+            ParserError::Lex(ref e) => Some(e),
+            _                       => None,
         }
     }
+}
 
-    fn cause(&self) -> Option<&Error> {
-        None
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnknownCharacter(char),
+    MalformedNumber(String),
+    NumberOutOfRange(String),
+    InvalidSuffix(String),
+    UnterminatedString,
+    BadEscape(char),
+    MalformedEscape(String),
+    EmptyInterpolation,
+    UnterminatedComment,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LexError::UnknownCharacter(c)      => write!(f, "Unknown character '{}'", c),
+            LexError::MalformedNumber(ref n)   => write!(f, "Malformed number '{}'", n),
+            LexError::NumberOutOfRange(ref n)  => write!(f, "Number '{}' is out of range for its type", n),
+            LexError::InvalidSuffix(ref n)     => write!(f, "Invalid suffix on number '{}'", n),
+            LexError::UnterminatedString       => write!(f, "Unterminated string"),
+            LexError::BadEscape(c)             => write!(f, "Unknown escape sequence '\\{}'", c),
+            LexError::MalformedEscape(ref e)   => write!(f, "Malformed escape sequence '{}'", e),
+            LexError::EmptyInterpolation       => write!(f, "Empty interpolation '{{}}' in string"),
+            LexError::UnterminatedComment      => write!(f, "Unterminated block comment"),
+        }
     }
-}
\ No newline at end of file
+}
+
+impl Error for LexError {}
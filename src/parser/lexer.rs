@@ -1,19 +1,78 @@
-use std::str::Chars;
+use parser::span::{Span, Spanned};
+use parser::error::LexError;
+
+use std::str::CharIndices;
 use std::iter::Peekable;
+use std::fmt;
 
 pub struct Lexer<'a> {
-    input: Peekable<Chars<'a>>,
+    source:   &'a str,
+    input:    Peekable<CharIndices<'a>>,
+    base:     usize,
+    offset:   usize,
+    line:     usize,
+    column:   usize,
+    // every comment passed over, doc comments included, as written
+    comments: Vec<Spanned<String>>,
+    // whether the last token ends an operand, after which `-` subtracts rather than negates
+    operand:  bool,
+    // whether the last token was a `-` negating what follows
+    negating: bool,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Lexer<'a> {
+        Self::at(input, 0, 1, 1)
+    }
+
+    // lexes a slice of a larger source, e.g. the code inside `{...}` in a string, keeping outer positions
+    fn at(input: &'a str, offset: usize, line: usize, column: usize) -> Lexer<'a> {
         Lexer {
-            input: input.chars().peekable(),
+            source:   input,
+            input:    input.char_indices().peekable(),
+            base:     offset,
+            offset:   offset,
+            line:     line,
+            column:   column,
+            comments: Vec::new(),
+            operand:  false,
+            negating: false,
         }
     }
 
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    pub fn comments(&self) -> &[Spanned<String>] {
+        &self.comments
+    }
+
+    // whether only whitespace comes before `offset` on its line
+    fn starts_line(&self, offset: usize) -> bool {
+        self.source[.. offset - self.base].chars().rev().take_while(|&c| c != '\n').all(char::is_whitespace)
+    }
+
     fn read_char(&mut self) -> Option<char> {
-        self.input.next()
+        let (i, c) = match self.input.next() {
+            Some(next) => next,
+            None       => return None,
+        };
+
+        self.offset = self.base + i + c.len_utf8();
+
+        if c == '\n' {
+            self.line  += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn span_from(&self, start: usize, line: usize, column: usize) -> Span {
+        Span::new(start, self.offset, line, column)
     }
 
     fn read_identifier(&mut self, c: char) -> String {
@@ -22,7 +81,7 @@ impl<'a> Lexer<'a> {
         ident.push(c);
 
         while let Some(&c) = self.peek_char() {
-            if Self::is_letter_related(c) {
+            if Self::is_letter_related(c) || c.is_digit(10) {
                 ident.push(self.read_char().unwrap());
             } else {
                 break;
@@ -32,179 +91,798 @@ impl<'a> Lexer<'a> {
         ident
     }
 
-    fn read_number(&mut self, c: char) -> String {
-        let mut number = String::new();
+    fn read_number(&mut self, c: char) -> Result<Token, LexError> {
+        let radix = match (c, self.peek_char()) {
+            ('0', Some(&'x')) => 16,
+            ('0', Some(&'b')) => 2,
+            ('0', Some(&'o')) => 8,
+            _                 => 10,
+        };
+
+        if radix != 10 {
+            self.read_char();
+
+            return self.read_radix_number(radix)
+        }
+
+        let negated = self.negating;
+
+        let mut number   = String::new();
+        let mut is_float = false;
 
         number.push(c);
 
+        self.read_digits(&mut number, 10);
+
+        if self.peek_char() == Some(&'.') && self.peek_nth(1).map_or(false, |c| c.is_digit(10)) {
+            is_float = true;
+            number.push(self.read_char().unwrap());
+
+            self.read_digits(&mut number, 10);
+        }
+
+        if let Some(&e) = self.peek_char() {
+            let signed = match self.peek_nth(1) {
+                Some('+') | Some('-') => 2,
+                _                     => 1,
+            };
+
+            if (e == 'e' || e == 'E') && self.peek_nth(signed).map_or(false, |c| c.is_digit(10)) {
+                is_float = true;
+
+                for _ in 0 .. signed {
+                    number.push(self.read_char().unwrap());
+                }
+
+                self.read_digits(&mut number, 10);
+            }
+        }
+
+        // things like `1.2.3`
+        if self.peek_char() == Some(&'.') && self.peek_nth(1).map_or(false, |c| c.is_digit(10)) {
+            while let Some(&c) = self.peek_char() {
+                if Self::is_number_related(c, true) || c.is_alphanumeric() {
+                    number.push(self.read_char().unwrap());
+                } else {
+                    break
+                }
+            }
+
+            return Err(LexError::MalformedNumber(number))
+        }
+
+        let suffix = self.read_number_suffix(&number)?;
+        let digits = number.replace('_', "");
+
+        match suffix {
+            Some(ref t) if is_float && !t.is_float() => {
+                Err(LexError::InvalidSuffix(format!("{}{}", number, t)))
+            }
+
+            _ if is_float || suffix.map_or(false, |t| t.is_float()) => {
+                Self::float_literal(&number, &digits, suffix)
+            }
+
+            _ => match u64::from_str_radix(&digits, 10) {
+                Ok(value) => Self::integer_literal(&number, value, suffix, negated),
+                Err(_)    => Err(LexError::NumberOutOfRange(number)),
+            }
+        }
+    }
+
+    fn read_radix_number(&mut self, radix: u32) -> Result<Token, LexError> {
+        let prefix = match radix {
+            16 => "0x",
+            8  => "0o",
+            _  => "0b",
+        };
+
+        let negated    = self.negating;
+        let mut number = prefix.to_string();
+
+        self.read_digits(&mut number, radix);
+
+        let digits = number[2 ..].replace('_', "");
+
+        // trailing digits that don't belong to the radix, e.g. `0b102`
+        if self.peek_char().map_or(false, |c| c.is_digit(10)) || digits.is_empty() {
+            while let Some(&c) = self.peek_char() {
+                if c.is_alphanumeric() || c == '_' {
+                    number.push(self.read_char().unwrap());
+                } else {
+                    break
+                }
+            }
+
+            return Err(LexError::MalformedNumber(number))
+        }
+
+        let suffix = self.read_number_suffix(&number)?;
+
+        if suffix.map_or(false, |t| t.is_float()) {
+            return Err(LexError::InvalidSuffix(format!("{}{}", number, suffix.unwrap())))
+        }
+
+        match u64::from_str_radix(&digits, radix) {
+            Ok(value) => Self::integer_literal(&number, value, suffix, negated),
+            Err(_)    => Err(LexError::NumberOutOfRange(number)),
+        }
+    }
+
+    fn read_digits(&mut self, number: &mut String, radix: u32) {
         while let Some(&c) = self.peek_char() {
-            if Self::is_number_related(c, number.len() > 0) {
+            if c.is_digit(radix) || c == '_' {
                 number.push(self.read_char().unwrap());
             } else {
-                break;
+                break
+            }
+        }
+    }
+
+    fn read_number_suffix(&mut self, number: &str) -> Result<Option<NumberType>, LexError> {
+        match self.peek_char() {
+            Some(&c) if Self::is_letter_related(c) => (),
+            _                                       => return Ok(None),
+        }
+
+        let mut suffix = String::new();
+
+        while let Some(&c) = self.peek_char() {
+            if Self::is_letter_related(c) || c.is_digit(10) {
+                suffix.push(self.read_char().unwrap());
+            } else {
+                break
             }
         }
 
-        number
+        match NumberType::from_suffix(&suffix) {
+            Some(t) => Ok(Some(t)),
+            None    => Err(LexError::InvalidSuffix(format!("{}{}", number, suffix))),
+        }
+    }
+
+    // a negated signed literal may be one past the positive max, e.g. `-128i8`, which negation wraps
+    // back to the minimum. literals without a suffix take the type of where they're used, code
+    // generation checks them against it
+    fn integer_literal(number: &str, value: u64, suffix: Option<NumberType>, negated: bool) -> Result<Token, LexError> {
+        let minimum = negated as u64;
+
+        let max = match suffix {
+            Some(NumberType::I8)  => i8::MAX as u64 + minimum,
+            Some(NumberType::I16) => i16::MAX as u64 + minimum,
+            Some(NumberType::I32) => i32::MAX as u64 + minimum,
+            Some(NumberType::I64) => i64::MAX as u64 + minimum,
+            Some(NumberType::U8)  => u8::MAX as u64,
+            Some(NumberType::U16) => u16::MAX as u64,
+            Some(NumberType::U32) => u32::MAX as u64,
+            _                     => u64::MAX,
+        };
+
+        if value > max {
+            let suffix = suffix.map_or(String::new(), |t| t.to_string());
+
+            return Err(LexError::NumberOutOfRange(format!("{}{}", number, suffix)))
+        }
+
+        // `u64` literals above `i64::MAX` keep their bit pattern
+        Ok(Token::Integer(value as i64, suffix))
+    }
+
+    fn float_literal(number: &str, digits: &str, suffix: Option<NumberType>) -> Result<Token, LexError> {
+        let value = match digits.parse::<f64>() {
+            Ok(value) => value,
+            Err(_)    => return Err(LexError::MalformedNumber(number.to_string())),
+        };
+
+        let in_range = match suffix {
+            Some(NumberType::F32) => value <= ::std::f32::MAX as f64,
+            _                     => value.is_finite(),
+        };
+
+        if in_range {
+            Ok(Token::Float(value, suffix))
+        } else {
+            let suffix = suffix.map_or(String::new(), |t| t.to_string());
+
+            Err(LexError::NumberOutOfRange(format!("{}{}", number, suffix)))
+        }
     }
 
-    pub fn read_string(&mut self, enclosure: char) -> String {
-        let mut result  = Vec::new();
-        let mut escaped = false;
+    // called after the opening quote; a doubled quote right after it opens a triple-quoted string.
+    // `{...}` in non-raw strings is lexed as code, turning the literal into a `Token::Interpolation`
+    pub fn read_string(&mut self, enclosure: char, raw: bool) -> Result<Token, LexError> {
+        let triple = self.peek_char() == Some(&enclosure) && self.peek_nth(1) == Some(enclosure);
+
+        if triple {
+            self.read_char();
+            self.read_char();
+        }
+
+        let mut result = String::new();
+        let mut parts  = Vec::new();
+        let mut error  = None;
 
-        while let Some(n) = self.input.next() {
+        while let Some(n) = self.read_char() {
             match n {
-                '\\' if escaped => {
-                    result.push('\\');
-                    escaped = !escaped;
+                '{'  if !raw => match self.read_interpolation() {
+                    Ok((code, span)) => {
+                        if !result.is_empty() {
+                            parts.push(TextPart::Literal(result));
+                            result = String::new();
+                        }
+
+                        parts.push(TextPart::Code(code, span));
+                    }
+
+                    Err(e) => if error.is_none() {
+                        error = Some(e)
+                    },
+                },
+
+                '\\' if !raw => match self.read_escape() {
+                    Ok(c)  => result.push(c),
+
+                    // keep reading up to the closing quote, so lexing can resume after it
+                    Err(e) => if error.is_none() {
+                        error = Some(e)
+                    },
+                },
+
+                x if x == enclosure => {
+                    if triple {
+                        if self.peek_char() != Some(&enclosure) || self.peek_nth(1) != Some(enclosure) {
+                            result.push(x);
+                            continue
+                        }
+
+                        self.read_char();
+                        self.read_char();
+                    }
+
+                    if let Some(error) = error {
+                        return Err(error)
+                    }
+
+                    if parts.is_empty() {
+                        return Ok(Token::Text(result))
+                    }
+
+                    if !result.is_empty() {
+                        parts.push(TextPart::Literal(result));
+                    }
+
+                    return Ok(Token::Interpolation(parts))
+                }
+
+                _ => result.push(n),
+            }
+        }
+
+        Err(LexError::UnterminatedString)
+    }
+
+    // called after the `{`, reads up to the matching `}` and lexes what's in between, which it also spans
+    fn read_interpolation(&mut self) -> Result<(Vec<Spanned<Token>>, Span), LexError> {
+        let (start, line, column) = (self.offset, self.line, self.column);
+
+        let mut depth = 1;
+        let mut quote = None;
+
+        loop {
+            let c = match self.read_char() {
+                Some(c) => c,
+                None    => return Err(LexError::UnterminatedString),
+            };
+
+            match quote {
+                Some(q) => match c {
+                    '\\'         => { self.read_char(); }
+                    x if x == q  => quote = None,
+                    _            => (),
+                },
+
+                None    => match c {
+                    '"' | '\'' => quote = Some(c),
+                    '{'        => depth += 1,
+                    '}'        => {
+                        depth -= 1;
+
+                        if depth == 0 {
+                            break
+                        }
+                    }
+
+                    _          => (),
+                },
+            }
+        }
+
+        let code = &self.source[start - self.base .. self.offset - self.base - 1];
+        let span = Span::new(start, self.offset - 1, line, column);
+
+        if code.trim().is_empty() {
+            return Err(LexError::EmptyInterpolation)
+        }
+
+        let (tokens, mut errors) = Lexer::at(code, start, line, column).tokenize();
+
+        if errors.is_empty() {
+            Ok((tokens, span))
+        } else {
+            Err(errors.remove(0).node)
+        }
+    }
+
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        let n = match self.read_char() {
+            Some(n) => n,
+            None    => return Err(LexError::UnterminatedString),
+        };
+
+        match n {
+            '\\' => Ok('\\'),
+            't'  => Ok('\t'),
+            'n'  => Ok('\n'),
+            'r'  => Ok('\r'),
+            '0'  => Ok('\0'),
+            '"'  => Ok('"'),
+            '\'' => Ok('\''),
+            '{'  => Ok('{'),
+            '}'  => Ok('}'),
+
+            'x'  => {
+                let mut digits = String::new();
+
+                for _ in 0 .. 2 {
+                    match self.peek_char() {
+                        Some(&c) if c.is_digit(16) => digits.push(self.read_char().unwrap()),
+                        _                          => break,
+                    }
                 }
 
-                't' if escaped => {
-                    result.push('\t');
-                    escaped = false;
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 && byte < 0x80 => Ok(byte as char),
+                    _ => Err(LexError::MalformedEscape(format!("\\x{}", digits))),
                 }
+            }
 
-                'n' if escaped => {
-                    result.push('\n');
-                    escaped = false;
+            'u'  => {
+                let mut digits = String::new();
+
+                if self.peek_char() != Some(&'{') {
+                    return Err(LexError::MalformedEscape("\\u".to_string()))
                 }
 
-                x if enclosure == x => {
-                    if escaped {
-                        result.push(x)
+                self.read_char();
+
+                while let Some(&c) = self.peek_char() {
+                    if c.is_digit(16) {
+                        digits.push(self.read_char().unwrap());
                     } else {
                         break
                     }
                 }
 
-                _ => {
-                    escaped = false;
-                    result.push(n);
+                let escape = format!("\\u{{{}}}", digits);
+
+                if self.peek_char() != Some(&'}') || digits.is_empty() || digits.len() > 6 {
+                    return Err(LexError::MalformedEscape(escape))
+                }
+
+                self.read_char();
+
+                match u32::from_str_radix(&digits, 16).ok().and_then(::std::char::from_u32) {
+                    Some(c) => Ok(c),
+                    None    => Err(LexError::MalformedEscape(escape)),
                 }
             }
+
+            _    => Err(LexError::BadEscape(n)),
         }
+    }
+
+    // called after the opening `#`, only doc comments (`##`) produce a token
+    fn read_comment(&mut self) -> Result<Option<String>, LexError> {
+        match self.peek_char() {
+            Some(&'#') => {
+                self.read_char();
+
+                let mut doc = String::new();
+
+                while let Some(&c) = self.peek_char() {
+                    if c == '\n' {
+                        break
+                    }
+
+                    doc.push(self.read_char().unwrap());
+                }
+
+                if doc.starts_with(' ') {
+                    doc.remove(0);
+                }
+
+                Ok(Some(doc.trim_end().to_string()))
+            }
+
+            Some(&'[') => {
+                self.read_char();
+
+                let mut depth = 1;
+
+                while let Some(c) = self.read_char() {
+                    match (c, self.peek_char().cloned()) {
+                        ('#', Some('[')) => {
+                            self.read_char();
+                            depth += 1;
+                        }
+
+                        (']', Some('#')) => {
+                            self.read_char();
+                            depth -= 1;
+
+                            if depth == 0 {
+                                return Ok(None)
+                            }
+                        }
+
+                        _ => (),
+                    }
+                }
+
+                Err(LexError::UnterminatedComment)
+            }
+
+            _ => {
+                while let Some(&c) = self.peek_char() {
+                    if c == '\n' {
+                        break
+                    }
+
+                    self.read_char();
+                }
 
-        let out: String = result.iter().cloned().collect();
-        
-        out
+                Ok(None)
+            }
+        }
     }
 
-    fn get_precedence(token: &Token) -> i32 {
+    // binding power of infix operators, -1 for anything that isn't one
+    pub fn get_precedence(token: &Token) -> i32 {
         match *token {
-            Token::Assign      => 10,
-            Token::And         => 12,
-            Token::LessThan    => 15,
-            Token::GreaterThan => 15,
-            Token::EqualTo     => 15,
-            Token::NotEqualTo  => 15,
-            Token::Plus        => 20,
-            Token::Minus       => 20,
-            Token::Slash       => 40,
-            Token::Asterix     => 40,
-            Token::Period      => 100,
+            Token::Assign        => 10,
+            Token::PlusAssign    => 10,
+            Token::MinusAssign   => 10,
+            Token::AsterixAssign => 10,
+            Token::SlashAssign   => 10,
+            Token::PercentAssign => 10,
+            Token::Or            => 11,
+            Token::And           => 12,
+            Token::DotDot        => 13,
+            Token::LessThan      => 15,
+            Token::GreaterThan   => 15,
+            Token::LessEqual     => 15,
+            Token::GreaterEqual  => 15,
+            Token::EqualTo       => 15,
+            Token::NotEqualTo    => 15,
+            Token::BinaryOr      => 16,
+            Token::Caret         => 17,
+            Token::BinaryAnd     => 18,
+            Token::ShiftLeft     => 19,
+            Token::ShiftRight    => 19,
+            Token::Plus          => 20,
+            Token::Minus         => 20,
+            Token::Slash         => 40,
+            Token::Asterix       => 40,
+            Token::Percent       => 40,
+            Token::Power         => 60,
+            Token::Period        => 100,
+            Token::LBracket      => 100,
             _ => -1,
         }
     }
 
-    pub fn next_token(&mut self) -> Option<Token> {
-        self.skip_whitespace();
+    // binding power of prefix operators, between multiplication and `**` so `-2 ** 2` is `-(2 ** 2)`
+    pub fn get_prefix_precedence(token: &Token) -> i32 {
+        match *token {
+            Token::Minus  => 50,
+            Token::Denial => 50,
+            _ => -1,
+        }
+    }
 
-        if let Some(c) = self.read_char() {
-            match c {
-                '=' => {
-                    match self.peek_char() {
-                        Some(&'=') => {
-                            self.read_char();
-                            Some(Token::EqualTo)
-                        }
-                        
-                        _ => {
-                            Some(Token::Assign)
-                        }
+    pub fn get_associativity(token: &Token) -> Associativity {
+        match *token {
+            Token::Assign        |
+            Token::PlusAssign    |
+            Token::MinusAssign   |
+            Token::AsterixAssign |
+            Token::SlashAssign   |
+            Token::PercentAssign |
+            Token::Power         => Associativity::Right,
+            _                    => Associativity::Left,
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Result<Spanned<Token>, Spanned<LexError>>> {
+        loop {
+            self.skip_whitespace();
+
+            let (start, line, column) = (self.offset, self.line, self.column);
+
+            let c = match self.read_char() {
+                Some(c) => c,
+                None    => return None,
+            };
+
+            let token = match c {
+                '#' => {
+                    let comment = self.read_comment();
+
+                    if comment.is_ok() {
+                        let text = &self.source[start - self.base .. self.offset - self.base];
+
+                        self.comments.push(Spanned::new(text.trim_end().to_string(), self.span_from(start, line, column)));
+                    }
+
+                    // `##` only documents what follows when it starts its line, after code it's a plain comment
+                    match comment {
+                        Ok(Some(doc)) if self.starts_line(start) => Ok(Token::DocComment(doc)),
+                        Ok(_)                                    => continue,
+                        Err(error)                               => Err(error),
                     }
                 }
 
-                '+' => Some(Token::Plus),
-                '-' => {
-                    match self.peek_char() {
-                        Some(&'>') => {
-                            self.read_char();
-                            Some(Token::Arrow)
-                        }
-                        
-                        _ => {
-                            Some(Token::Minus)
-                        }
+                _   => self.read_token(c),
+            };
+
+            let span = self.span_from(start, line, column);
+
+            if let Ok(ref token) = token {
+                self.negating = *token == Token::Minus && !self.operand;
+                self.operand  = match *token {
+                    Token::Identifier(_)    |
+                    Token::Integer(..)      |
+                    Token::Float(..)        |
+                    Token::Text(_)          |
+                    Token::Interpolation(_) |
+                    Token::RParen           |
+                    Token::RBracket         |
+                    Token::True             |
+                    Token::False            => true,
+                    _                       => false,
+                };
+            }
+
+            return Some(match token {
+                Ok(token)  => Ok(Spanned::new(token, span)),
+                Err(error) => Err(Spanned::new(error, span)),
+            })
+        }
+    }
+
+    // lexes the whole input, carrying on past errors so every one of them gets reported
+    pub fn tokenize(&mut self) -> (Vec<Spanned<Token>>, Vec<Spanned<LexError>>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+
+        for result in self {
+            match result {
+                Ok(token)  => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        (tokens, errors)
+    }
+
+    fn read_token(&mut self, c: char) -> Result<Token, LexError> {
+        match c {
+            '=' => {
+                match self.peek_char() {
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::EqualTo)
                     }
-                },
+                    
+                    _ => {
+                        Ok(Token::Assign)
+                    }
+                }
+            }
 
-                '~' => {
-                    match self.peek_char() {
-                        Some(&'=') => {
-                            self.read_char();
-                            Some(Token::NotEqualTo)
-                        }
-                        
-                        _ => {
-                            Some(Token::Denial)
-                        }
+            '+' => {
+                match self.peek_char() {
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::PlusAssign)
+                    }
+
+                    _ => {
+                        Ok(Token::Plus)
                     }
                 }
+            }
 
-                '&' => {
-                    match self.peek_char() {
-                        Some(&'&') => {
-                            self.read_char();
-                            Some(Token::And)
-                        }
-                        
-                        _ => {
-                            Some(Token::BinaryAnd)
-                        }
+            '-' => {
+                match self.peek_char() {
+                    Some(&'>') => {
+                        self.read_char();
+                        Ok(Token::Arrow)
+                    }
+
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::MinusAssign)
+                    }
+                    
+                    _ => {
+                        Ok(Token::Minus)
+                    }
+                }
+            },
+
+            '~' => {
+                match self.peek_char() {
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::NotEqualTo)
+                    }
+                    
+                    _ => {
+                        Ok(Token::Denial)
                     }
                 }
+            }
+
+            '&' => {
+                match self.peek_char() {
+                    Some(&'&') => {
+                        self.read_char();
+                        Ok(Token::And)
+                    }
+                    
+                    _ => {
+                        Ok(Token::BinaryAnd)
+                    }
+                }
+            }
 
-                '*' => Some(Token::Asterix),
-                '/' => Some(Token::Slash),
-                '<' => Some(Token::LessThan),
-                '>' => Some(Token::GreaterThan),
-                ',' => Some(Token::Comma),
-                '.' => Some(Token::Period),
+            '|' => {
+                match self.peek_char() {
+                    Some(&'|') => {
+                        self.read_char();
+                        Ok(Token::Or)
+                    }
+
+                    _ => {
+                        Ok(Token::BinaryOr)
+                    }
+                }
+            }
 
-                ':' => Some(Token::Colon),
+            '*' => {
+                match self.peek_char() {
+                    Some(&'*') => {
+                        self.read_char();
+                        Ok(Token::Power)
+                    }
 
-                '(' => Some(Token::LParen),
-                ')' => Some(Token::RParen),
-                '{' => Some(Token::LBrace),
-                '}' => Some(Token::RBrace),
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::AsterixAssign)
+                    }
 
-                '"' | '\'' => {
-                    Some(Token::Text(self.read_string('"')))
+                    _ => {
+                        Ok(Token::Asterix)
+                    }
                 }
+            }
 
-                _   => {
-                    if Self::is_letter_related(c) {
-                        Some(Self::string_to_keyword(self.read_identifier(c)))
-                    } else if c.is_digit(10) {
-                        let number: String = self.read_number(c);
-
-                        match number.parse::<i64>() {
-                            Ok(int) => Some(Token::Integer(int)),
-                            _       => {
-                                match number.parse::<f64>() {
-                                    Ok(float) => Some(Token::Float(float)),
-                                    _         => panic!("Invalid number!")
-                                }
-                            }
-                        }
+            '/' => {
+                match self.peek_char() {
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::SlashAssign)
+                    }
 
-                    } else {
-                        Some(Token::Fucked(c))
+                    _ => {
+                        Ok(Token::Slash)
                     }
                 }
             }
-        } else {
-            None
+
+            '%' => {
+                match self.peek_char() {
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::PercentAssign)
+                    }
+
+                    _ => {
+                        Ok(Token::Percent)
+                    }
+                }
+            }
+
+            '<' => {
+                match self.peek_char() {
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::LessEqual)
+                    }
+
+                    Some(&'<') => {
+                        self.read_char();
+                        Ok(Token::ShiftLeft)
+                    }
+
+                    _ => {
+                        Ok(Token::LessThan)
+                    }
+                }
+            }
+
+            '>' => {
+                match self.peek_char() {
+                    Some(&'=') => {
+                        self.read_char();
+                        Ok(Token::GreaterEqual)
+                    }
+
+                    Some(&'>') => {
+                        self.read_char();
+                        Ok(Token::ShiftRight)
+                    }
+
+                    _ => {
+                        Ok(Token::GreaterThan)
+                    }
+                }
+            }
+
+            '^' => Ok(Token::Caret),
+            ',' => Ok(Token::Comma),
+            '.' => {
+                match self.peek_char() {
+                    Some(&'.') => {
+                        self.read_char();
+                        Ok(Token::DotDot)
+                    }
+
+                    _ => {
+                        Ok(Token::Period)
+                    }
+                }
+            }
+
+            ':' => Ok(Token::Colon),
+
+            '(' => Ok(Token::LParen),
+            ')' => Ok(Token::RParen),
+            '{' => Ok(Token::LBrace),
+            '}' => Ok(Token::RBrace),
+            '[' => Ok(Token::LBracket),
+            ']' => Ok(Token::RBracket),
+
+            '"' | '\'' => {
+                self.read_string(c, false)
+            }
+
+            'r' if self.peek_char() == Some(&'"') || self.peek_char() == Some(&'\'') => {
+                let enclosure = self.read_char().unwrap();
+
+                self.read_string(enclosure, true)
+            }
+
+            _   => {
+                if Self::is_letter_related(c) {
+                    Ok(Self::string_to_keyword(self.read_identifier(c)))
+                } else if c.is_digit(10) {
+                    self.read_number(c)
+                } else {
+                    Err(LexError::UnknownCharacter(c))
+                }
+            }
         }
     }
 
@@ -220,21 +898,31 @@ impl<'a> Lexer<'a> {
 
     fn string_to_keyword(id: String) -> Token {
         match id.as_str() {
-            "func"   => Token::Function,
-            "lambda" => Token::Lambda,
-            "if"     => Token::If,
-            "unless" => Token::Unless,
-            "else"   => Token::Else,
-            "return" => Token::Return,
-            "end"    => Token::End,
-            "true"   => Token::True,
-            "false"  => Token::False,
-            _        => Token::Identifier(id),
+            "func"     => Token::Function,
+            "lambda"   => Token::Lambda,
+            "if"       => Token::If,
+            "unless"   => Token::Unless,
+            "else"     => Token::Else,
+            "while"    => Token::While,
+            "until"    => Token::Until,
+            "for"      => Token::For,
+            "in"       => Token::In,
+            "break"    => Token::Break,
+            "continue" => Token::Continue,
+            "return"   => Token::Return,
+            "end"      => Token::End,
+            "true"     => Token::True,
+            "false"    => Token::False,
+            _          => Token::Identifier(id),
         }
     }
 
     fn peek_char(&mut self) -> Option<&char> {
-        self.input.peek()
+        self.input.peek().map(|&(_, ref c)| c)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.source[self.offset - self.base ..].chars().nth(n)
     }
 
     fn skip_whitespace(&mut self) {
@@ -249,31 +937,50 @@ impl<'a> Lexer<'a> {
 }
 
 impl<'a> Iterator for Lexer<'a> {
-    type Item = Token;
+    type Item = Result<Spanned<Token>, Spanned<LexError>>;
 
-    fn next(&mut self) -> Option<Token> {
+    fn next(&mut self) -> Option<Result<Spanned<Token>, Spanned<LexError>>> {
         self.next_token()
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Token {
-    Fucked(char),
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextPart {
+    Literal(String),
+    // the tokens between the braces, and the span of what's between them
+    Code(Vec<Spanned<Token>>, Span),
+}
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
     Identifier(String),
-    Integer(i64),
-    Float(f64),
+    Integer(i64, Option<NumberType>),
+    Float(f64, Option<NumberType>),
     Text(String),
+    Interpolation(Vec<TextPart>),
+    DocComment(String),
+    // stands in for what failed to lex, so the parser can skip the statement around it
+    Error,
 
     Assign,
+    PlusAssign,
+    MinusAssign,
+    AsterixAssign,
+    SlashAssign,
+    PercentAssign,
+
     Denial,
     Asterix,
     Minus,
     Plus,
     Slash,
+    Percent,
+    Power,
 
     GreaterThan,
     LessThan,
+    GreaterEqual,
+    LessEqual,
     EqualTo,
     NotEqualTo,
 
@@ -285,20 +992,358 @@ pub enum Token {
     RBrace,
     LParen,
     RParen,
+    LBracket,
+    RBracket,
 
     Period,
+    DotDot,
 
     Function,
     Lambda,
     If,
     Unless,
     Else,
+    While,
+    Until,
+    For,
+    In,
+    Break,
+    Continue,
     Return,
     End,
 
     And,
+    Or,
     BinaryAnd,
+    BinaryOr,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
 
     True,
     False,
-}
\ No newline at end of file
+}
+
+// how tokens are named in error messages
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match *self {
+            Token::Identifier(ref name)    => return write!(f, "identifier `{}`", name),
+            Token::Integer(n, Some(t))     => return write!(f, "number `{}{}`", n as u64, t),
+            Token::Integer(n, None)        => return write!(f, "number `{}`", n as u64),
+            Token::Float(n, Some(t))       => return write!(f, "number `{}{}`", n, t),
+            Token::Float(n, None)          => return write!(f, "number `{}`", n),
+            Token::Text(_)                 |
+            Token::Interpolation(_)        => return write!(f, "string"),
+            Token::DocComment(_)           => return write!(f, "doc comment"),
+            Token::Error                   => return write!(f, "invalid token"),
+
+            Token::Assign        => "=",
+            Token::PlusAssign    => "+=",
+            Token::MinusAssign   => "-=",
+            Token::AsterixAssign => "*=",
+            Token::SlashAssign   => "/=",
+            Token::PercentAssign => "%=",
+            Token::Denial        => "~",
+            Token::Asterix       => "*",
+            Token::Minus         => "-",
+            Token::Plus          => "+",
+            Token::Slash         => "/",
+            Token::Percent       => "%",
+            Token::Power         => "**",
+            Token::GreaterThan   => ">",
+            Token::LessThan      => "<",
+            Token::GreaterEqual  => ">=",
+            Token::LessEqual     => "<=",
+            Token::EqualTo       => "==",
+            Token::NotEqualTo    => "~=",
+            Token::Comma         => ",",
+            Token::Colon         => ":",
+            Token::Arrow         => "->",
+            Token::LBrace        => "{",
+            Token::RBrace        => "}",
+            Token::LParen        => "(",
+            Token::RParen        => ")",
+            Token::LBracket      => "[",
+            Token::RBracket      => "]",
+            Token::Period        => ".",
+            Token::DotDot        => "..",
+            Token::Function      => "func",
+            Token::Lambda        => "lambda",
+            Token::If            => "if",
+            Token::Unless        => "unless",
+            Token::Else          => "else",
+            Token::While         => "while",
+            Token::Until         => "until",
+            Token::For           => "for",
+            Token::In            => "in",
+            Token::Break         => "break",
+            Token::Continue      => "continue",
+            Token::Return        => "return",
+            Token::End           => "end",
+            Token::And           => "&&",
+            Token::Or            => "||",
+            Token::BinaryAnd     => "&",
+            Token::BinaryOr      => "|",
+            Token::Caret         => "^",
+            Token::ShiftLeft     => "<<",
+            Token::ShiftRight    => ">>",
+            Token::True          => "true",
+            Token::False         => "false",
+        };
+
+        write!(f, "`{}`", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+// explicit literal widths, e.g. `255u8` or `1.5f32`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+impl NumberType {
+    pub fn from_suffix(suffix: &str) -> Option<NumberType> {
+        match suffix {
+            "i8"  => Some(NumberType::I8),
+            "i16" => Some(NumberType::I16),
+            "i32" => Some(NumberType::I32),
+            "i64" => Some(NumberType::I64),
+            "u8"  => Some(NumberType::U8),
+            "u16" => Some(NumberType::U16),
+            "u32" => Some(NumberType::U32),
+            "u64" => Some(NumberType::U64),
+            "f32" => Some(NumberType::F32),
+            "f64" => Some(NumberType::F64),
+            _     => None,
+        }
+    }
+
+    // matches the `int8_type` .. `int64_type`, `float_type` and `double_type` widths in `vm::llvm_type`
+    pub fn bits(&self) -> u32 {
+        match *self {
+            NumberType::I8  | NumberType::U8  => 8,
+            NumberType::I16 | NumberType::U16 => 16,
+            NumberType::I32 | NumberType::U32 |
+            NumberType::F32                   => 32,
+            _                                 => 64,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        *self == NumberType::F32 || *self == NumberType::F64
+    }
+
+    pub fn is_signed(&self) -> bool {
+        match *self {
+            NumberType::U8  | NumberType::U16 |
+            NumberType::U32 | NumberType::U64 => false,
+            _                                 => true,
+        }
+    }
+}
+
+impl fmt::Display for NumberType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let suffix = match *self {
+            NumberType::I8  => "i8",
+            NumberType::I16 => "i16",
+            NumberType::I32 => "i32",
+            NumberType::I64 => "i64",
+            NumberType::U8  => "u8",
+            NumberType::U16 => "u16",
+            NumberType::U32 => "u32",
+            NumberType::U64 => "u64",
+            NumberType::F32 => "f32",
+            NumberType::F64 => "f64",
+        };
+
+        write!(f, "{}", suffix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parser::error::LexError;
+
+    fn lex(source: &str) -> (Vec<Token>, Vec<LexError>) {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+
+        (tokens.into_iter().map(|t| t.node).collect(), errors.into_iter().map(|e| e.node).collect())
+    }
+
+    // the tokens of source that must lex without errors
+    fn tokens(source: &str) -> Vec<Token> {
+        let (tokens, errors) = lex(source);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        tokens
+    }
+
+    fn ident(name: &str) -> Token {
+        Token::Identifier(name.to_string())
+    }
+
+    fn text(text: &str) -> Token {
+        Token::Text(text.to_string())
+    }
+
+    #[test]
+    fn tokens_and_spans() {
+        let (tokens, _) = Lexer::new("x: int = 1\n  foo(a, b) -> bar").tokenize();
+        let spans       = tokens.iter().map(|t| (t.span.start, t.span.end, t.span.line, t.span.column)).collect::<Vec<_>>();
+
+        assert_eq!(tokens.into_iter().map(|t| t.node).collect::<Vec<_>>(), vec![
+            ident("x"), Token::Colon, ident("int"), Token::Assign, Token::Integer(1, None),
+            ident("foo"), Token::LParen, ident("a"), Token::Comma, ident("b"), Token::RParen, Token::Arrow, ident("bar"),
+        ]);
+
+        assert_eq!(spans, vec![
+            (0, 1, 1, 1), (1, 2, 1, 2), (3, 6, 1, 4), (7, 8, 1, 8), (9, 10, 1, 10),
+            (13, 16, 2, 3), (16, 17, 2, 6), (17, 18, 2, 7), (18, 19, 2, 8), (20, 21, 2, 10), (21, 22, 2, 11), (23, 25, 2, 13), (26, 29, 2, 16),
+        ]);
+    }
+
+    #[test]
+    fn operators_and_keywords() {
+        assert_eq!(tokens("+= -= *= /= %= ** == ~= <= >= << >> && || & | ^ ~ .. ."), vec![
+            Token::PlusAssign, Token::MinusAssign, Token::AsterixAssign, Token::SlashAssign, Token::PercentAssign,
+            Token::Power, Token::EqualTo, Token::NotEqualTo, Token::LessEqual, Token::GreaterEqual,
+            Token::ShiftLeft, Token::ShiftRight, Token::And, Token::Or, Token::BinaryAnd, Token::BinaryOr,
+            Token::Caret, Token::Denial, Token::DotDot, Token::Period,
+        ]);
+
+        assert_eq!(tokens("func lambda if unless else while until for in break continue return end true false ends"), vec![
+            Token::Function, Token::Lambda, Token::If, Token::Unless, Token::Else, Token::While, Token::Until,
+            Token::For, Token::In, Token::Break, Token::Continue, Token::Return, Token::End, Token::True,
+            Token::False, ident("ends"),
+        ]);
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(tokens("a # one\n#[ two #[ nested ]# ]# b\n## doc\nc ## not doc"), vec![
+            ident("a"), ident("b"), Token::DocComment("doc".to_string()), ident("c"),
+        ]);
+
+        let mut lexer = Lexer::new("a # one\n## doc\nc ## not doc");
+
+        lexer.tokenize();
+
+        assert_eq!(lexer.comments().iter().map(|c| c.node.as_str()).collect::<Vec<_>>(), vec!["# one", "## doc", "## not doc"]);
+        assert_eq!(lex("a #[ open"), (vec![ident("a")], vec![LexError::UnterminatedComment]));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(tokens("0x1F 0o17 0b101 1_000_000 0xFF_u8 7i16"), vec![
+            Token::Integer(31, None), Token::Integer(15, None), Token::Integer(5, None),
+            Token::Integer(1_000_000, None), Token::Integer(255, Some(NumberType::U8)), Token::Integer(7, Some(NumberType::I16)),
+        ]);
+
+        assert_eq!(tokens("1.5 2e3 1.5e-3f32 3f64 18446744073709551615"), vec![
+            Token::Float(1.5, None), Token::Float(2e3, None), Token::Float(1.5e-3, Some(NumberType::F32)),
+            Token::Float(3.0, Some(NumberType::F64)), Token::Integer(-1, None),
+        ]);
+
+        // a range, not a float
+        assert_eq!(tokens("0..10"), vec![Token::Integer(0, None), Token::DotDot, Token::Integer(10, None)]);
+
+        assert_eq!(lex("0b102 1.5u8 3i7 256u8 0x").1, vec![
+            LexError::MalformedNumber("0b102".to_string()),
+            LexError::InvalidSuffix("1.5u8".to_string()),
+            LexError::InvalidSuffix("3i7".to_string()),
+            LexError::NumberOutOfRange("256u8".to_string()),
+            LexError::MalformedNumber("0x".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn negated_signed_minimums() {
+        assert_eq!(tokens("-128i8"), vec![Token::Minus, Token::Integer(128, Some(NumberType::I8))]);
+        assert_eq!(tokens("(-9223372036854775808i64)"), vec![
+            Token::LParen, Token::Minus, Token::Integer(i64::MIN, Some(NumberType::I64)), Token::RParen,
+        ]);
+
+        // after an operand `-` subtracts, so the literal has to fit by itself
+        assert_eq!(lex("x -128i8").1, vec![LexError::NumberOutOfRange("128i8".to_string())]);
+        assert_eq!(lex("128i8").1, vec![LexError::NumberOutOfRange("128i8".to_string())]);
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(tokens(r#""a\tb\n\"\x41\u{e9}\{" 'single' r"raw\n{x}" "#), vec![
+            text("a\tb\n\"A\u{e9}{"), text("single"), text("raw\\n{x}"),
+        ]);
+
+        assert_eq!(tokens("\"\"\"two \"quoted\"\nlines\"\"\" ''''''"), vec![text("two \"quoted\"\nlines"), text("")]);
+
+        assert_eq!(lex(r#""\q" "\x8" "\u{110000}" "open"#).1, vec![
+            LexError::BadEscape('q'),
+            LexError::MalformedEscape("\\x8".to_string()),
+            LexError::MalformedEscape("\\u{110000}".to_string()),
+            LexError::UnterminatedString,
+        ]);
+    }
+
+    #[test]
+    fn interpolation() {
+        let (tokens, _) = Lexer::new("\"a {b + 1} c {'}'}\"").tokenize();
+
+        match tokens[0].node {
+            Token::Interpolation(ref parts) => {
+                assert_eq!(parts.len(), 4);
+                assert_eq!(parts[0], TextPart::Literal("a ".to_string()));
+                assert_eq!(parts[2], TextPart::Literal(" c ".to_string()));
+
+                match parts[1] {
+                    TextPart::Code(ref code, span) => {
+                        assert_eq!(code.iter().map(|t| t.node.clone()).collect::<Vec<_>>(), vec![ident("b"), Token::Plus, Token::Integer(1, None)]);
+                        assert_eq!((code[0].span.start, code[0].span.column), (4, 5));
+                        assert_eq!((span.start, span.end), (4, 9));
+                    }
+
+                    ref other => panic!("{:?}", other),
+                }
+
+                match parts[3] {
+                    TextPart::Code(ref code, _) => assert_eq!(code[0].node, text("}")),
+                    ref other                   => panic!("{:?}", other),
+                }
+            }
+
+            ref other => panic!("{:?}", other),
+        }
+
+        assert_eq!(lex("\"a {} b\"").1, vec![LexError::EmptyInterpolation]);
+    }
+
+    #[test]
+    fn errors_are_skipped() {
+        let (tokens, errors) = Lexer::new("a $ b \"\\q\" c").tokenize();
+
+        assert_eq!(tokens.into_iter().map(|t| t.node).collect::<Vec<_>>(), vec![ident("a"), ident("b"), ident("c")]);
+        assert_eq!(errors.iter().map(|e| (e.node.clone(), e.span.start, e.span.end)).collect::<Vec<_>>(), vec![
+            (LexError::UnknownCharacter('$'), 2, 3),
+            (LexError::BadEscape('q'), 6, 10),
+        ]);
+    }
+}
@@ -1,3 +1,7 @@
+pub mod span;
 pub mod lexer;
 pub mod parser;
+pub mod visitor;
+pub mod capture;
 pub mod error;
+pub mod diagnostic;
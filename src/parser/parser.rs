@@ -1,56 +1,1399 @@
-use parser::lexer::{Token, Lexer};
+use parser::lexer::{Token, TextPart, NumberType, Lexer, Associativity};
 use parser::error::ParserError;
+use parser::span::{Span, Spanned};
+use parser::capture;
+
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub items: Vec<Spanned<Item>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Function(Function),
+    Statement(Statement),
+}
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name:   String,
-    pub params: Vec<String>,
+    pub params: Vec<Param>,
     pub body:   Box<Statement>,
-    pub ret:    String,
+    pub ret:    Type,
+    pub doc:    Option<String>,
+}
+
+// an anonymous function, `captures` names the variables it uses from around it
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub params:   Vec<Param>,
+    pub body:     Box<Statement>,
+    pub ret:      Type,
+    pub captures: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty:   Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Void,
+    // explicitly sized numbers, `i8` .. `u64`, `f32` and `f64`
+    Number(NumberType),
+    Pointer(Box<Type>),
+    // `[4]int`, or `[]int` when the length isn't known
+    Array(Box<Type>, Option<usize>),
+    Function(Vec<Type>, Box<Type>),
+    Named(String),
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Type {
+        match name {
+            "int"   => Type::Int,
+            "float" => Type::Float,
+            "bool"  => Type::Bool,
+            "str"   => Type::Str,
+            "void"  => Type::Void,
+            _       => match NumberType::from_suffix(name) {
+                Some(t) => Type::Number(t),
+                None    => Type::Named(name.to_string()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Int                       => write!(f, "int"),
+            Type::Float                     => write!(f, "float"),
+            Type::Bool                      => write!(f, "bool"),
+            Type::Str                       => write!(f, "str"),
+            Type::Void                      => write!(f, "void"),
+            Type::Number(ref t)             => write!(f, "{}", t),
+            Type::Pointer(ref t)            => write!(f, "*{}", t),
+            Type::Array(ref t, Some(len))   => write!(f, "[{}]{}", len, t),
+            Type::Array(ref t, None)        => write!(f, "[]{}", t),
+            Type::Named(ref name)           => write!(f, "{}", name),
+            Type::Function(ref params, ref ret) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+
+                write!(f, "func ({}) -> {}", params.join(", "), ret)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Statement {
-    If(Box<Expr>, Box<Statement>),
-    IfElse(Box<Expr>, Box<Statement>, Box<Statement>),
-    Var(String, Option<Box<Expr>>),
-    Block(Box<Vec<Statement>>),
-    Expr(Box<Expr>),
+    If(Box<Spanned<Expr>>, Box<Statement>),
+    IfElse(Box<Spanned<Expr>>, Box<Statement>, Box<Statement>),
+    Unless(Box<Spanned<Expr>>, Box<Statement>),
+    UnlessElse(Box<Spanned<Expr>>, Box<Statement>, Box<Statement>),
+    While(Box<Spanned<Expr>>, Box<Statement>),
+    Until(Box<Spanned<Expr>>, Box<Statement>),
+    // `for i in 0..n` and `for x in array`
+    For(String, Box<Spanned<Expr>>, Box<Statement>),
+    Break,
+    Continue,
+    Var(String, Type, Option<Box<Spanned<Expr>>>),
+    Block(Box<Vec<Spanned<Statement>>>),
+    Expr(Box<Spanned<Expr>>),
     Return,
-    ReturnWithVal(Box<Expr>),
+    ReturnWithVal(Box<Spanned<Expr>>),
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
-    Integer(i64),
-    Float(f64),
+    Integer(i64, Option<NumberType>),
+    Float(f64, Option<NumberType>),
     Text(String),
+    Interpolation(Vec<InterpolationPart>),
     Identifier(String),
-    FnCall(String, Box<Vec<Expr>>),
-    Dot(Box<Expr>, Box<Expr>),
-    Index(String, Box<Expr>),
-    Assignment(Box<Expr>, Box<Expr>),
+    FnCall(String, Box<Vec<Spanned<Expr>>>),
+    Dot(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Index(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Assignment(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    CompoundAssignment(BinaryOp, Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Binary(Box<Spanned<Expr>>, BinaryOp, Box<Spanned<Expr>>),
+    Unary(UnaryOp, Box<Spanned<Expr>>),
+    // `a..b`, from `a` up to but not including `b`
+    Range(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Lambda(Lambda),
+    // an `if` or `unless` statement with an `else`, yielding the last expression of the branch taken
+    Conditional(Box<Statement>),
     True,
     False,
 }
 
-fn parse_main<'a>(input: &mut Lexer<'a>) -> Result<Expr, ParserError> {
-    if let Some(token) = input.next_token() {
-        match token {
-            Token::Integer(ref a) => Ok(Expr::Integer(a.clone())),
-            Token::Text(ref a)    => Ok(Expr::Text(a.clone())),
-            _                     => panic!("Unimplemented parser expression"),
+#[derive(Debug, Clone)]
+pub enum InterpolationPart {
+    Literal(String),
+    Expr(Box<Spanned<Expr>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOp {
+    pub fn from_token(token: &Token) -> Option<BinaryOp> {
+        match *token {
+            Token::Plus         => Some(BinaryOp::Add),
+            Token::Minus        => Some(BinaryOp::Sub),
+            Token::Asterix      => Some(BinaryOp::Mul),
+            Token::Slash        => Some(BinaryOp::Div),
+            Token::Percent      => Some(BinaryOp::Mod),
+            Token::Power        => Some(BinaryOp::Pow),
+            Token::EqualTo      => Some(BinaryOp::Equal),
+            Token::NotEqualTo   => Some(BinaryOp::NotEqual),
+            Token::LessThan     => Some(BinaryOp::Less),
+            Token::GreaterThan  => Some(BinaryOp::Greater),
+            Token::LessEqual    => Some(BinaryOp::LessEqual),
+            Token::GreaterEqual => Some(BinaryOp::GreaterEqual),
+            Token::And          => Some(BinaryOp::And),
+            Token::Or           => Some(BinaryOp::Or),
+            Token::BinaryAnd    => Some(BinaryOp::BitAnd),
+            Token::BinaryOr     => Some(BinaryOp::BitOr),
+            Token::Caret        => Some(BinaryOp::BitXor),
+            Token::ShiftLeft    => Some(BinaryOp::ShiftLeft),
+            Token::ShiftRight   => Some(BinaryOp::ShiftRight),
+            _                   => None,
+        }
+    }
+
+    // the operator a compound assignment like `+=` applies
+    pub fn from_assignment(token: &Token) -> Option<BinaryOp> {
+        match *token {
+            Token::PlusAssign    => Some(BinaryOp::Add),
+            Token::MinusAssign   => Some(BinaryOp::Sub),
+            Token::AsterixAssign => Some(BinaryOp::Mul),
+            Token::SlashAssign   => Some(BinaryOp::Div),
+            Token::PercentAssign => Some(BinaryOp::Mod),
+            _                    => None,
+        }
+    }
+
+    pub fn to_token(&self) -> Token {
+        match *self {
+            BinaryOp::Add          => Token::Plus,
+            BinaryOp::Sub          => Token::Minus,
+            BinaryOp::Mul          => Token::Asterix,
+            BinaryOp::Div          => Token::Slash,
+            BinaryOp::Mod          => Token::Percent,
+            BinaryOp::Pow          => Token::Power,
+            BinaryOp::Equal        => Token::EqualTo,
+            BinaryOp::NotEqual     => Token::NotEqualTo,
+            BinaryOp::Less         => Token::LessThan,
+            BinaryOp::Greater      => Token::GreaterThan,
+            BinaryOp::LessEqual    => Token::LessEqual,
+            BinaryOp::GreaterEqual => Token::GreaterEqual,
+            BinaryOp::And          => Token::And,
+            BinaryOp::Or           => Token::Or,
+            BinaryOp::BitAnd       => Token::BinaryAnd,
+            BinaryOp::BitOr        => Token::BinaryOr,
+            BinaryOp::BitXor       => Token::Caret,
+            BinaryOp::ShiftLeft    => Token::ShiftLeft,
+            BinaryOp::ShiftRight   => Token::ShiftRight,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match *self {
+            BinaryOp::Add          => "+",
+            BinaryOp::Sub          => "-",
+            BinaryOp::Mul          => "*",
+            BinaryOp::Div          => "/",
+            BinaryOp::Mod          => "%",
+            BinaryOp::Pow          => "**",
+            BinaryOp::Equal        => "==",
+            BinaryOp::NotEqual     => "~=",
+            BinaryOp::Less         => "<",
+            BinaryOp::Greater      => ">",
+            BinaryOp::LessEqual    => "<=",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::And          => "&&",
+            BinaryOp::Or           => "||",
+            BinaryOp::BitAnd       => "&",
+            BinaryOp::BitOr        => "|",
+            BinaryOp::BitXor       => "^",
+            BinaryOp::ShiftLeft    => "<<",
+            BinaryOp::ShiftRight   => ">>",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+}
+
+impl UnaryOp {
+    pub fn from_token(token: &Token) -> Option<UnaryOp> {
+        match *token {
+            Token::Minus  => Some(UnaryOp::Negate),
+            Token::Denial => Some(UnaryOp::Not),
+            _             => None,
+        }
+    }
+
+    pub fn to_token(&self) -> Token {
+        match *self {
+            UnaryOp::Negate => Token::Minus,
+            UnaryOp::Not    => Token::Denial,
+        }
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnaryOp::Negate => write!(f, "-"),
+            UnaryOp::Not    => write!(f, "~"),
+        }
+    }
+}
+
+pub type ParseResult<T> = Result<T, Spanned<ParserError>>;
+
+pub struct Parser {
+    tokens:   Vec<Spanned<Token>>,
+    position: usize,
+    errors:   Vec<Spanned<ParserError>>,
+    // how many loops enclose what's being parsed, within the innermost function
+    loops:    usize,
+    // what the tokens came from, to point at when there are none
+    span:     Span,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Spanned<Token>>) -> Parser {
+        Parser {
+            tokens:   tokens,
+            position: 0,
+            errors:   Vec::new(),
+            loops:    0,
+            span:     Span::default(),
+        }
+    }
+
+    // parses the tokens of a part of the source, e.g. the code inside `{...}` in a string
+    fn within(tokens: Vec<Spanned<Token>>, span: Span) -> Parser {
+        Parser {
+            span: span,
+            ..Parser::new(tokens)
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn peek_token(&self) -> Option<&Spanned<Token>> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_token(&mut self) -> Option<Spanned<Token>> {
+        let token = self.tokens.get(self.position).cloned();
+
+        if token.is_some() {
+            self.position += 1;
+        }
+
+        token
+    }
+
+    fn peek_is(&self, token: &Token) -> bool {
+        self.peek_token().map_or(false, |t| t.node == *token)
+    }
+
+    // the span of the last consumed token
+    fn previous_span(&self) -> Span {
+        match self.position {
+            0 => Span::default(),
+            n => self.tokens[n - 1].span,
+        }
+    }
+
+    // where the next token starts, or just past the input when there's none left
+    fn here(&self) -> Span {
+        match self.peek_token() {
+            Some(token) => token.span,
+            None        => match self.tokens.last() {
+                Some(last) => Span::new(last.span.end, last.span.end, last.span.line, last.span.column + last.span.len()),
+                None       => self.span,
+            },
+        }
+    }
+
+    fn error_here<T>(&self, error: ParserError) -> ParseResult<T> {
+        Err(Spanned::new(error, self.here()))
+    }
+
+    // the token at `here()`, `None` at the end of the input
+    fn found(&self) -> Option<Token> {
+        self.peek_token().map(|t| t.node.clone())
+    }
+
+    fn previous_token(&self) -> Option<Token> {
+        match self.position {
+            0 => None,
+            n => Some(self.tokens[n - 1].node.clone()),
+        }
+    }
+
+    // `expected` should have come next, running out of input is reported as such
+    fn unexpected(&self, expected: &str) -> Spanned<ParserError> {
+        let error = match self.found() {
+            Some(found) => ParserError::Expected { expected: expected.to_string(), found: found },
+            None        => ParserError::OutOfInput { expected: expected.to_string() },
+        };
+
+        Spanned::new(error, self.here())
+    }
+
+    fn expected_here<T>(&self, expected: &str) -> ParseResult<T> {
+        Err(self.unexpected(expected))
+    }
+
+    // `expected` should have been the token that was just consumed
+    fn expected_previous<T>(&self, expected: &str) -> ParseResult<T> {
+        let found = match self.previous_token() {
+            Some(found) => found,
+            None        => return self.expected_here(expected),
+        };
+
+        Err(Spanned::new(ParserError::Expected { expected: expected.to_string(), found: found }, self.previous_span()))
+    }
+
+    fn expect(&mut self, token: Token) -> ParseResult<Spanned<Token>> {
+        match self.next_token() {
+            Some(ref t) if t.node == token => Ok(t.clone()),
+            Some(_)                        => self.expected_previous(&token.to_string()),
+            None                           => self.expected_here(&token.to_string()),
+        }
+    }
+
+    fn expect_identifier(&mut self, expected: &str) -> ParseResult<Spanned<String>> {
+        match self.next_token() {
+            Some(Spanned { node: Token::Identifier(name), span }) => Ok(Spanned::new(name, span)),
+            Some(_) => self.expected_previous(expected),
+            None    => self.expected_here(expected),
+        }
+    }
+
+    fn report(&mut self, error: Spanned<ParserError>) {
+        // running out of input gets reported by every block still open, once is enough
+        if self.errors.last() != Some(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    // skips what's left of a statement that failed to parse: the rest of the line it broke on, and the
+    // bodies of blocks it opened, so their `end`s don't close the surrounding block too early. a statement
+    // holding a token that failed to lex already has its error, anything it broke into after is left out
+    fn recover(&mut self, start: usize, error: Spanned<ParserError>) {
+        let line = error.span.line;
+
+        if self.position == start {
+            self.next_token();
+        }
+
+        let mut depth = 0;
+
+        for i in start .. self.position {
+            depth += self.block_depth(i);
+        }
+
+        while let Some(token) = self.peek_token().cloned() {
+            if token.span.line > line {
+                break
+            }
+
+            match token.node {
+                Token::End | Token::Else if depth <= 0 => break,
+                _                                      => depth += self.block_depth(self.position),
+            }
+
+            self.next_token();
+        }
+
+        if !self.tokens[start .. self.position].iter().any(|t| t.node == Token::Error) {
+            self.report(error);
+        }
+
+        for _ in 0 .. depth {
+            self.skip_block();
+        }
+    }
+
+    // how the token at `index` changes the number of open blocks
+    fn block_depth(&self, index: usize) -> i32 {
+        match self.tokens[index].node {
+            // `func` also starts function types, which never begin a line and are never named
+            Token::Function => {
+                let named      = match self.tokens.get(index + 1) {
+                    Some(&Spanned { node: Token::Identifier(_), .. }) => true,
+                    _                                                 => false,
+                };
+
+                let line_start = index == 0 || self.tokens[index - 1].span.line < self.tokens[index].span.line;
+
+                if named || line_start { 1 } else { 0 }
+            }
+
+            // `else if` continues the chain of the first `if`, sharing its `end`
+            Token::If | Token::Unless => if self.chained(index) { 0 } else { 1 },
+
+            Token::While | Token::Until | Token::For | Token::Lambda => 1,
+            Token::End                                               => -1,
+            _                                                        => 0,
+        }
+    }
+
+    // whether the `if` or `unless` at `index` directly follows an `else`
+    fn chained(&self, index: usize) -> bool {
+        index > 0 && self.tokens[index - 1].node == Token::Else
+                  && self.tokens[index - 1].span.line == self.tokens[index].span.line
+    }
+
+    fn skip_block(&mut self) {
+        loop {
+            if let Err(error) = self.parse_block() {
+                self.report(error);
+                return
+            }
+
+            if !self.peek_is(&Token::Else) {
+                break
+            }
+
+            let line = self.next_token().unwrap().span.line;
+
+            // the condition of an `else if`
+            while self.peek_token().map_or(false, |t| t.span.line == line) {
+                self.next_token();
+            }
+        }
+
+        if self.peek_is(&Token::End) {
+            self.next_token();
+        }
+    }
+
+    // consecutive `##` lines, joined, for whatever item follows them
+    fn parse_doc(&mut self) -> Option<String> {
+        let mut lines = Vec::new();
+
+        while let Some(Spanned { node: Token::DocComment(line), .. }) = self.peek_token().cloned() {
+            self.next_token();
+            lines.push(line);
+        }
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    // parses as much as possible, returning what could be parsed along with every error on the way
+    pub fn parse_program(&mut self) -> (Program, Vec<Spanned<ParserError>>) {
+        let mut items = Vec::new();
+
+        loop {
+            let doc = self.parse_doc();
+
+            let span = match self.peek_token() {
+                Some(token) => token.span,
+                None        => break,
+            };
+
+            let start = self.position;
+
+            let item = if self.peek_is(&Token::Function) {
+                self.parse_function(doc).map(Item::Function)
+            } else {
+                self.parse_statement().map(|s| Item::Statement(s.node))
+            };
+
+            match item {
+                Ok(item)   => items.push(Spanned::new(item, span.to(self.previous_span()))),
+                Err(error) => self.recover(start, error),
+            }
+        }
+
+        let program = Program {
+            items: items,
+        };
+
+        (program, ::std::mem::replace(&mut self.errors, Vec::new()))
+    }
+
+    fn parse_function(&mut self, doc: Option<String>) -> ParseResult<Function> {
+        self.expect(Token::Function)?;
+
+        let name = match self.peek_token().cloned() {
+            Some(Spanned { node: Token::Identifier(name), .. }) => {
+                self.next_token();
+                name
+            }
+
+            found => return self.error_here(ParserError::FnMissingName(found.map(|t| t.node))),
+        };
+
+        if !self.peek_is(&Token::LParen) {
+            return self.error_here(ParserError::FnMissingParameters(self.found()))
+        }
+
+        let (params, ret) = self.parse_signature()?;
+        let body          = self.parse_body()?;
+
+        self.expect(Token::End)?;
+
+        Ok(Function {
+            name:   name,
+            params: params,
+            body:   Box::new(body),
+            ret:    ret,
+            doc:    doc,
+        })
+    }
+
+    // `(a: int, b: int) -> int`, functions without an arrow return nothing
+    fn parse_signature(&mut self) -> ParseResult<(Vec<Param>, Type)> {
+        self.expect(Token::LParen)?;
+
+        let mut params = Vec::new();
+
+        if self.peek_is(&Token::RParen) {
+            self.next_token();
+        } else {
+            loop {
+                let param = self.expect_identifier("a parameter name")?;
+
+                self.expect(Token::Colon)?;
+
+                params.push(Param {
+                    name: param.node,
+                    ty:   self.parse_type()?,
+                });
+
+                match self.next_token() {
+                    Some(Spanned { node: Token::Comma, .. })  => continue,
+                    Some(Spanned { node: Token::RParen, .. }) => break,
+                    Some(t) => return Err(Spanned::new(ParserError::MissingRParen(Some(t.node)), t.span)),
+                    None    => return self.error_here(ParserError::MissingRParen(None)),
+                }
+            }
+        }
+
+        let ret = if self.peek_is(&Token::Arrow) {
+            self.next_token();
+            self.parse_type()?
+        } else {
+            Type::Void
+        };
+
+        Ok((params, ret))
+    }
+
+    fn parse_type(&mut self) -> ParseResult<Type> {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None        => return self.expected_here("a type"),
+        };
+
+        match token.node {
+            Token::Identifier(name) => Ok(Type::from_name(&name)),
+
+            Token::Asterix => Ok(Type::Pointer(Box::new(self.parse_type()?))),
+
+            // `**int` lexes as the power operator
+            Token::Power   => Ok(Type::Pointer(Box::new(Type::Pointer(Box::new(self.parse_type()?))))),
+
+            Token::LBracket => {
+                let len = match self.next_token() {
+                    Some(Spanned { node: Token::RBracket, .. }) => None,
+
+                    Some(Spanned { node: Token::Integer(len, None), .. }) if len >= 0 => {
+                        self.expect(Token::RBracket)?;
+                        Some(len as usize)
+                    }
+
+                    Some(_) => return self.expected_previous("an array length or `]`"),
+                    None    => return self.expected_here("an array length or `]`"),
+                };
+
+                Ok(Type::Array(Box::new(self.parse_type()?), len))
+            }
+
+            // `func (int, int) -> int`
+            Token::Function => {
+                self.expect(Token::LParen)?;
+
+                let mut params = Vec::new();
+
+                if self.peek_is(&Token::RParen) {
+                    self.next_token();
+                } else {
+                    loop {
+                        params.push(self.parse_type()?);
+
+                        match self.next_token() {
+                            Some(Spanned { node: Token::Comma, .. })  => continue,
+                            Some(Spanned { node: Token::RParen, .. }) => break,
+                            Some(t) => return Err(Spanned::new(ParserError::MissingRParen(Some(t.node)), t.span)),
+                            None    => return self.error_here(ParserError::MissingRParen(None)),
+                        }
+                    }
+                }
+
+                let ret = if self.peek_is(&Token::Arrow) {
+                    self.next_token();
+                    self.parse_type()?
+                } else {
+                    Type::Void
+                };
+
+                Ok(Type::Function(params, Box::new(ret)))
+            }
+
+            _ => self.expected_previous("a type"),
+        }
+    }
+
+    // the block of a function or lambda, loops around it can't be left from inside
+    fn parse_body(&mut self) -> ParseResult<Statement> {
+        let loops = ::std::mem::replace(&mut self.loops, 0);
+        let body  = self.parse_block();
+
+        self.loops = loops;
+
+        body
+    }
+
+    fn parse_loop_body(&mut self) -> ParseResult<Statement> {
+        self.loops += 1;
+
+        let body = self.parse_block();
+
+        self.loops -= 1;
+
+        body
+    }
+
+    // statements up to, but not including, the `end` or `else` closing the block
+    fn parse_block(&mut self) -> ParseResult<Statement> {
+        let mut statements = Vec::new();
+
+        loop {
+            self.parse_doc();
+
+            match self.peek_token() {
+                Some(&Spanned { node: Token::End, .. })  |
+                Some(&Spanned { node: Token::Else, .. }) => break,
+                Some(_) => (),
+                None    => return self.expected_here("`end`"),
+            }
+
+            let start = self.position;
+
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error)    => self.recover(start, error),
+            }
+        }
+
+        Ok(Statement::Block(Box::new(statements)))
+    }
+
+    fn parse_statement(&mut self) -> ParseResult<Spanned<Statement>> {
+        let token = match self.peek_token() {
+            Some(token) => token.clone(),
+            None        => return self.expected_here("a statement"),
+        };
+
+        let statement = match token.node {
+            Token::If | Token::Unless => {
+                self.next_token();
+
+                let conditional = self.parse_conditional(token.node == Token::Unless)?;
+
+                self.expect(Token::End)?;
+
+                conditional
+            }
+
+            Token::While | Token::Until => {
+                self.next_token();
+
+                let condition = self.parse_expression()?;
+                let body      = self.parse_loop_body()?;
+
+                self.expect(Token::End)?;
+
+                if token.node == Token::While {
+                    Statement::While(Box::new(condition), Box::new(body))
+                } else {
+                    Statement::Until(Box::new(condition), Box::new(body))
+                }
+            }
+
+            Token::For => {
+                self.next_token();
+
+                let name = self.expect_identifier("a loop variable")?;
+
+                self.expect(Token::In)?;
+
+                let iterable = self.parse_expression()?;
+                let body     = self.parse_loop_body()?;
+
+                self.expect(Token::End)?;
+
+                Statement::For(name.node, Box::new(iterable), Box::new(body))
+            }
+
+            Token::Break | Token::Continue => {
+                self.next_token();
+
+                if self.loops == 0 {
+                    return Err(Spanned::new(ParserError::OutsideLoop(token.node), token.span))
+                }
+
+                if token.node == Token::Break {
+                    Statement::Break
+                } else {
+                    Statement::Continue
+                }
+            }
+
+            Token::Return => {
+                self.next_token();
+
+                let has_value = match self.peek_token() {
+                    Some(next) => next.span.line == token.span.line
+                                  && next.node != Token::End
+                                  && next.node != Token::Else,
+                    None       => false,
+                };
+
+                if has_value {
+                    Statement::ReturnWithVal(Box::new(self.parse_expression()?))
+                } else {
+                    Statement::Return
+                }
+            }
+
+            // `name: type` and `name: type = value` declare a variable
+            Token::Identifier(ref name) if self.tokens.get(self.position + 1).map_or(false, |t| t.node == Token::Colon) => {
+                self.next_token();
+                self.next_token();
+
+                let ty    = self.parse_type()?;
+                let value = if self.peek_is(&Token::Assign) {
+                    self.next_token();
+                    Some(Box::new(self.parse_expression()?))
+                } else {
+                    None
+                };
+
+                Statement::Var(name.clone(), ty, value)
+            }
+
+            _ => Statement::Expr(Box::new(self.parse_expression()?)),
+        };
+
+        let span = token.span.to(self.previous_span());
+
+        // statements end with their line, unless a block closes right after them
+        let trailing = match self.peek_token() {
+            Some(next) => next.span.line == self.previous_span().line
+                          && next.node != Token::End
+                          && next.node != Token::Else,
+            None       => false,
+        };
+
+        if trailing {
+            let error = self.unexpected("the end of the line");
+            let start = self.position;
+
+            self.recover(start, error);
+        }
+
+        Ok(Spanned::new(statement, span))
+    }
+
+    // the rest of an `if` or `unless` after its keyword, up to the `end`. `else if` and `else unless` chain
+    // on, sharing that one `end`
+    fn parse_conditional(&mut self, unless: bool) -> ParseResult<Statement> {
+        let condition = Box::new(self.parse_expression()?);
+        let body      = Box::new(self.parse_block()?);
+
+        let line = match self.peek_token() {
+            Some(&Spanned { node: Token::Else, span }) => span.line,
+            _ if unless                                => return Ok(Statement::Unless(condition, body)),
+            _                                          => return Ok(Statement::If(condition, body)),
+        };
+
+        self.next_token();
+
+        let chained = match self.peek_token() {
+            Some(next) => (next.node == Token::If || next.node == Token::Unless) && next.span.line == line,
+            None       => false,
+        };
+
+        let otherwise = if chained {
+            let next = self.next_token().unwrap();
+
+            Box::new(self.parse_conditional(next.node == Token::Unless)?)
+        } else {
+            Box::new(self.parse_block()?)
+        };
+
+        if unless {
+            Ok(Statement::UnlessElse(condition, body, otherwise))
+        } else {
+            Ok(Statement::IfElse(condition, body, otherwise))
+        }
+    }
+
+    pub fn parse_expression(&mut self) -> ParseResult<Spanned<Expr>> {
+        self.parse_binary(0)
+    }
+
+    fn parse_binary(&mut self, min_precedence: i32) -> ParseResult<Spanned<Expr>> {
+        let mut lhs = self.parse_unary()?;
+
+        loop {
+            let (token, op_span) = match self.peek_token() {
+                Some(token) => (token.node.clone(), token.span),
+                None        => break,
+            };
+
+            let precedence = Lexer::get_precedence(&token);
+
+            // statements end with their line, an operator starting the next one begins something else
+            if precedence < 0 || precedence < min_precedence || op_span.line > self.previous_span().line {
+                break
+            }
+
+            self.next_token();
+
+            let next_precedence = match Lexer::get_associativity(&token) {
+                Associativity::Left  => precedence + 1,
+                Associativity::Right => precedence,
+            };
+
+            lhs = match token {
+                Token::Period   => {
+                    let member = match self.next_token() {
+                        Some(Spanned { node: Token::Identifier(name), span }) => Spanned::new(Expr::Identifier(name), span),
+                        Some(_) => return self.expected_previous("a member name"),
+                        None    => return self.expected_here("a member name"),
+                    };
+
+                    let span = lhs.span.to(member.span);
+
+                    Spanned::new(Expr::Dot(Box::new(lhs), Box::new(member)), span)
+                }
+
+                Token::LBracket => {
+                    let index = self.parse_expression()?;
+                    let close = match self.next_token() {
+                        Some(Spanned { node: Token::RBracket, span }) => span,
+                        Some(_) => return self.expected_previous("`]`"),
+                        None    => return self.expected_here("`]`"),
+                    };
+
+                    let span = lhs.span.to(close);
+
+                    Spanned::new(Expr::Index(Box::new(lhs), Box::new(index)), span)
+                }
+
+                Token::DotDot   => {
+                    let rhs  = self.parse_binary(next_precedence)?;
+                    let span = lhs.span.to(rhs.span);
+
+                    Spanned::new(Expr::Range(Box::new(lhs), Box::new(rhs)), span)
+                }
+
+                Token::Assign   => {
+                    Self::check_assignable(&lhs)?;
+
+                    let rhs  = self.parse_binary(next_precedence)?;
+                    let span = lhs.span.to(rhs.span);
+
+                    Spanned::new(Expr::Assignment(Box::new(lhs), Box::new(rhs)), span)
+                }
+
+                _ => {
+                    let rhs  = self.parse_binary(next_precedence)?;
+                    let span = lhs.span.to(rhs.span);
+
+                    if let Some(op) = BinaryOp::from_assignment(&token) {
+                        Self::check_assignable(&lhs)?;
+
+                        Spanned::new(Expr::CompoundAssignment(op, Box::new(lhs), Box::new(rhs)), span)
+                    } else {
+                        let op = match BinaryOp::from_token(&token) {
+                            Some(op) => op,
+                            None     => return Err(Spanned::new(ParserError::UnknownOperator(token), op_span)),
+                        };
+
+                        Spanned::new(Expr::Binary(Box::new(lhs), op, Box::new(rhs)), span)
+                    }
+                }
+            }
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> ParseResult<Spanned<Expr>> {
+        let token = match self.peek_token() {
+            Some(token) => token.clone(),
+            None        => return self.expected_here("an expression"),
+        };
+
+        if let Some(op) = UnaryOp::from_token(&token.node) {
+            self.next_token();
+
+            let operand = self.parse_binary(Lexer::get_prefix_precedence(&token.node))?;
+            let span    = token.span.to(operand.span);
+
+            return Ok(Spanned::new(Expr::Unary(op, Box::new(operand)), span))
+        }
+
+        // an infix operator where an operand should be, e.g. `* 2`
+        if Lexer::get_precedence(&token.node) >= 0 {
+            return self.error_here(ParserError::UnknownOperator(token.node))
+        }
+
+        self.parse_main()
+    }
+
+    fn parse_main(&mut self) -> ParseResult<Spanned<Expr>> {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None        => return self.expected_here("an expression"),
+        };
+
+        let span = token.span;
+
+        let expr = match token.node {
+            Token::Integer(a, t)        => Expr::Integer(a, t),
+            Token::Float(a, t)          => Expr::Float(a, t),
+            Token::Text(a)              => Expr::Text(a),
+            Token::Interpolation(parts) => Self::parse_interpolation(parts)?,
+            Token::Identifier(a)        => return self.parse_identifier(a, span),
+            Token::True                 => Expr::True,
+            Token::False                => Expr::False,
+
+            Token::Lambda => {
+                let (params, ret) = self.parse_signature()?;
+                let body          = self.parse_body()?;
+                let end           = self.expect(Token::End)?;
+                let captures      = capture::captures(&params, &body);
+
+                let lambda = Lambda {
+                    params:   params,
+                    body:     Box::new(body),
+                    ret:      ret,
+                    captures: captures,
+                };
+
+                return Ok(Spanned::new(Expr::Lambda(lambda), span.to(end.span)))
+            }
+
+            Token::If | Token::Unless => {
+                let conditional = self.parse_conditional(token.node == Token::Unless)?;
+
+                // without an `else` there'd be nothing to yield when the condition doesn't hold
+                match conditional {
+                    Statement::If(..) | Statement::Unless(..) => return self.expected_here("`else`"),
+                    _                                         => (),
+                }
+
+                let end = self.expect(Token::End)?;
+
+                return Ok(Spanned::new(Expr::Conditional(Box::new(conditional)), span.to(end.span)))
+            }
+
+            Token::LParen => {
+                let inner = self.parse_expression()?;
+
+                return match self.next_token() {
+                    Some(Spanned { node: Token::RParen, span: close }) => {
+                        Ok(Spanned::new(inner.node, span.to(close)))
+                    }
+
+                    Some(t) => Err(Spanned::new(ParserError::MissingRParen(Some(t.node)), t.span)),
+                    None    => self.error_here(ParserError::MissingRParen(None)),
+                }
+            }
+
+            // left in place, it may be the `end` closing the block around the expression
+            _ => {
+                self.position -= 1;
+
+                return self.expected_here("an expression")
+            }
+        };
+
+        Ok(Spanned::new(expr, span))
+    }
+
+    // `f(x, y)` when the paren touches the name, `f x, y` when the first argument follows on the same line
+    fn parse_identifier(&mut self, name: String, span: Span) -> ParseResult<Spanned<Expr>> {
+        let (adjacent_paren, juxtaposed) = match self.peek_token() {
+            Some(next) => (
+                next.node == Token::LParen && next.span.start == span.end,
+                next.span.line == span.line && Self::starts_argument(&next.node),
+            ),
+
+            None => (false, false),
+        };
+
+        let mut args = Vec::new();
+        let end;
+
+        if adjacent_paren {
+            self.next_token();
+
+            if let Some(Spanned { node: Token::RParen, .. }) = self.peek_token().cloned() {
+                end = self.next_token().unwrap().span;
+            } else {
+                loop {
+                    args.push(self.parse_expression()?);
+
+                    match self.next_token() {
+                        Some(Spanned { node: Token::Comma, .. })        => continue,
+                        Some(Spanned { node: Token::RParen, span: c }) => {
+                            end = c;
+                            break
+                        }
+
+                        Some(t) => return Err(Spanned::new(ParserError::MissingRParen(Some(t.node)), t.span)),
+                        None    => return self.error_here(ParserError::MissingRParen(None)),
+                    }
+                }
+            }
+        } else if juxtaposed {
+            // arguments bind looser than any operator but assignment, so `fib a - 1` is `fib(a - 1)`
+            let precedence = Lexer::get_precedence(&Token::Assign) + 1;
+
+            loop {
+                args.push(self.parse_binary(precedence)?);
+
+                match self.peek_token() {
+                    Some(&Spanned { node: Token::Comma, .. }) => { self.next_token(); }
+                    _                                        => break,
+                }
+            }
+
+            end = args[args.len() - 1].span;
+        } else {
+            return Ok(Spanned::new(Expr::Identifier(name), span))
+        }
+
+        Ok(Spanned::new(Expr::FnCall(name, Box::new(args)), span.to(end)))
+    }
+
+    // tokens that can open a juxtaposed argument, `-` is left out as `a - 1` is always a subtraction
+    fn starts_argument(token: &Token) -> bool {
+        match *token {
+            Token::Identifier(_)    |
+            Token::Integer(..)      |
+            Token::Float(..)        |
+            Token::Text(_)          |
+            Token::Interpolation(_) |
+            Token::True             |
+            Token::False            |
+            Token::Denial           |
+            Token::Lambda           |
+            Token::If               |
+            Token::Unless           |
+            Token::LParen           => true,
+            _                       => false,
+        }
+    }
+
+    fn parse_interpolation(parts: Vec<TextPart>) -> ParseResult<Expr> {
+        let mut result = Vec::new();
+
+        for part in parts {
+            result.push(match part {
+                TextPart::Literal(text)      => InterpolationPart::Literal(text),
+                TextPart::Code(tokens, span) => {
+                    let mut parser = Parser::within(tokens, span);
+                    let expr       = parser.parse_expression()?;
+
+                    // each `{...}` holds exactly one expression
+                    if !parser.is_done() {
+                        return parser.expected_here("`}`")
+                    }
+
+                    InterpolationPart::Expr(Box::new(expr))
+                }
+            })
+        }
+
+        Ok(Expr::Interpolation(result))
+    }
+
+    fn check_assignable(target: &Spanned<Expr>) -> ParseResult<()> {
+        match target.node {
+            Expr::Identifier(_) | Expr::Dot(..) | Expr::Index(..) => Ok(()),
+            _                                                     => Err(Spanned::new(ParserError::InvalidAssignment, target.span)),
+        }
+    }
+}
+
+// lexes and parses a whole source, lexical errors are reported alongside the parser's own
+pub fn parse_program(source: &str) -> (Program, Vec<Spanned<ParserError>>) {
+    let mut tokens     = Vec::new();
+    let mut lex_errors = Vec::new();
+
+    for result in Lexer::new(source) {
+        match result {
+            Ok(token)  => tokens.push(token),
+            Err(error) => {
+                tokens.push(Spanned::new(Token::Error, error.span));
+                lex_errors.push(error);
+            }
         }
-    } else {
-        Err(ParserError::OutOfInput)
     }
+
+    let (program, parse_errors) = Parser::new(tokens).parse_program();
+
+    let mut errors: Vec<Spanned<ParserError>> = lex_errors.into_iter()
+                                                          .map(|e| Spanned::new(ParserError::Lex(e.node), e.span))
+                                                          .collect();
+
+    errors.extend(parse_errors);
+    errors.sort_by_key(|e| e.span.start);
+
+    (program, errors)
 }
 
-fn parse_expression<'a>(input: &mut Lexer<'a>) -> Result<Expr, ParserError> {
-    let lhs = match parse_main(input) {
-        Ok(a)  => a,
-        Err(a) => panic!("{}", a),
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parser::error::LexError;
+    use dump;
 
-    Ok(Expr::True)
-}
\ No newline at end of file
+    // the syntax tree as an S-expression on one line, without spans
+    fn shape(source: &str) -> String {
+        let (program, errors) = parse_program(source);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let sexp = dump::program(&program).without_spans().to_sexp();
+
+        sexp.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn operators_on_the_next_line_start_a_new_statement() {
+        assert_eq!(
+            shape("sign = if a < 0\n    -1\nelse\n    1\nend\n"),
+            "(program :items ((expr :expr (assign :target (identifier :name \"sign\") \
+             :value (conditional :statement (if_else \
+             :condition (binary :op \"<\" :lhs (identifier :name \"a\") :rhs (integer :value 0 :suffix nil)) \
+             :then (block :statements ((expr :expr (unary :op \"-\" :operand (integer :value 1 :suffix nil))))) \
+             :else (block :statements ((expr :expr (integer :value 1 :suffix nil))))))))))",
+        );
+
+        assert_eq!(
+            shape("y: int = x\n-1\n"),
+            "(program :items ((var :name \"y\" :type \"int\" :value (identifier :name \"x\")) \
+             (expr :expr (unary :op \"-\" :operand (integer :value 1 :suffix nil)))))",
+        );
+    }
+
+    #[test]
+    fn doc_comments_after_code_are_plain_comments() {
+        assert_eq!(
+            shape("x: int = 1 ## note\nreturn 1 ## doc\n"),
+            "(program :items ((var :name \"x\" :type \"int\" :value (integer :value 1 :suffix nil)) \
+             (return :value (integer :value 1 :suffix nil))))",
+        );
+
+        assert_eq!(
+            shape("  ## one\n## two\nfunc f () -> void ## not three\nend\n"),
+            "(program :items ((function :name \"f\" :doc \"one\\ntwo\" :params () :ret \"void\" :body (block :statements ()))))",
+        );
+    }
+
+    #[test]
+    fn errors_in_interpolations_point_into_the_string() {
+        let (_, errors) = parse_program("s: str = \"a{# c}b\"\nt: str = \"{1 2}\"\n");
+        let spans       = errors.iter().map(|e| (e.span.start, e.span.end, e.span.line, e.span.column)).collect::<Vec<_>>();
+
+        assert_eq!(spans, vec![(12, 15, 1, 13), (32, 33, 2, 14)]);
+    }
+
+    // the errors parsing source reports, with the line and column each starts at
+    fn errors(source: &str) -> Vec<(ParserError, usize, usize)> {
+        parse_program(source).1.into_iter().map(|e| (e.node, e.span.line, e.span.column)).collect()
+    }
+
+    fn ident(name: &str) -> String {
+        format!("(identifier :name \"{}\")", name)
+    }
+
+    fn binary(op: &str, lhs: &str, rhs: &str) -> String {
+        format!("(binary :op \"{}\" :lhs {} :rhs {})", op, lhs, rhs)
+    }
+
+    // the single expression statement source parses to
+    fn expression(source: &str) -> String {
+        let program = shape(source);
+
+        program["(program :items ((expr :expr ".len() .. program.len() - ")))".len()].to_string()
+    }
+
+    #[test]
+    fn precedence() {
+        let (a, b, c, d, e) = (ident("a"), ident("b"), ident("c"), ident("d"), ident("e"));
+
+        assert_eq!(expression("a + b * c\n"), binary("+", &a, &binary("*", &b, &c)));
+        assert_eq!(expression("a * b + c\n"), binary("+", &binary("*", &a, &b), &c));
+        assert_eq!(expression("(a + b) * c\n"), binary("*", &binary("+", &a, &b), &c));
+        assert_eq!(expression("a && b || c == d & e\n"), binary("||", &binary("&&", &a, &b), &binary("==", &c, &binary("&", &d, &e))));
+        assert_eq!(expression("a << b + c\n"), binary("<<", &a, &binary("+", &b, &c)));
+
+        // prefix operators bind tighter than `*` but not `**`
+        assert_eq!(expression("-a * b\n"), binary("*", "(unary :op \"-\" :operand (identifier :name \"a\"))", &b));
+        assert_eq!(expression("-a ** b\n"), format!("(unary :op \"-\" :operand {})", binary("**", &a, &b)));
+        assert_eq!(expression("~a == b\n"), binary("==", "(unary :op \"~\" :operand (identifier :name \"a\"))", &b));
+    }
+
+    #[test]
+    fn associativity() {
+        let (a, b, c) = (ident("a"), ident("b"), ident("c"));
+
+        assert_eq!(expression("a - b - c\n"), binary("-", &binary("-", &a, &b), &c));
+        assert_eq!(expression("a / b / c\n"), binary("/", &binary("/", &a, &b), &c));
+        assert_eq!(expression("a ** b ** c\n"), binary("**", &a, &binary("**", &b, &c)));
+        assert_eq!(expression("a = b = c\n"), format!("(assign :target {} :value (assign :target {} :value {}))", a, b, c));
+    }
+
+    #[test]
+    fn calls() {
+        let (a, b, c) = (ident("a"), ident("b"), ident("c"));
+
+        assert_eq!(expression("f()\n"), "(call :name \"f\" :args ())");
+        assert_eq!(expression("f(a, b)\n"), format!("(call :name \"f\" :args ({} {}))", a, b));
+
+        // a juxtaposed call takes everything after it, including other juxtaposed calls
+        assert_eq!(expression("f a, g b, c\n"), format!("(call :name \"f\" :args ({} (call :name \"g\" :args ({} {}))))", a, b, c));
+        assert_eq!(expression("f a - 1\n"), format!("(call :name \"f\" :args ({}))", binary("-", &a, "(integer :value 1 :suffix nil)")));
+        assert_eq!(expression("(f a) + g b\n"), binary("+", &format!("(call :name \"f\" :args ({}))", a), &format!("(call :name \"g\" :args ({}))", b)));
+
+        // not a call, `-` after an operand subtracts
+        assert_eq!(expression("f -a\n"), binary("-", "(identifier :name \"f\")", &a));
+    }
+
+    #[test]
+    fn postfix() {
+        assert_eq!(
+            expression("x.y[1].z\n"),
+            "(dot :object (index :object (dot :object (identifier :name \"x\") :member (identifier :name \"y\")) \
+             :index (integer :value 1 :suffix nil)) :member (identifier :name \"z\"))",
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(
+            shape("if a\n  1\nelse if b\n  2\nelse\n  3\nend\n"),
+            "(program :items ((if_else :condition (identifier :name \"a\") \
+             :then (block :statements ((expr :expr (integer :value 1 :suffix nil)))) \
+             :else (if_else :condition (identifier :name \"b\") \
+             :then (block :statements ((expr :expr (integer :value 2 :suffix nil)))) \
+             :else (block :statements ((expr :expr (integer :value 3 :suffix nil))))))))",
+        );
+
+        assert_eq!(
+            shape("unless a\n  1\nelse\n  2\nend\nunless b\nend\n"),
+            "(program :items ((unless_else :condition (identifier :name \"a\") \
+             :then (block :statements ((expr :expr (integer :value 1 :suffix nil)))) \
+             :else (block :statements ((expr :expr (integer :value 2 :suffix nil))))) \
+             (unless :condition (identifier :name \"b\") :then (block :statements ()))))",
+        );
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(
+            shape("while a\n  break\nend\nuntil b\n  continue\nend\nfor i in 0..n\n  i\nend\n"),
+            "(program :items ((while :condition (identifier :name \"a\") :body (block :statements ((break)))) \
+             (until :condition (identifier :name \"b\") :body (block :statements ((continue)))) \
+             (for :variable \"i\" :iterable (range :start (integer :value 0 :suffix nil) :end (identifier :name \"n\")) \
+             :body (block :statements ((expr :expr (identifier :name \"i\")))))))",
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(errors("break\n"), vec![(ParserError::OutsideLoop(Token::Break), 1, 1)]);
+        assert_eq!(errors("x: int = )\n"), vec![(ParserError::Expected { expected: "an expression".to_string(), found: Token::RParen }, 1, 10)]);
+        assert_eq!(errors("1 = 2\n"), vec![(ParserError::InvalidAssignment, 1, 1)]);
+        assert_eq!(errors("f(a, b"), vec![(ParserError::MissingRParen(None), 1, 7)]);
+        assert_eq!(errors("z = 3 +\n"), vec![(ParserError::OutOfInput { expected: "an expression".to_string() }, 1, 8)]);
+        assert_eq!(errors("func (a) -> int\nend\n"), vec![(ParserError::FnMissingName(Some(Token::LParen)), 1, 6)]);
+        assert_eq!(errors("x = $\n"), vec![(ParserError::Lex(LexError::UnknownCharacter('$')), 1, 5)]);
+    }
+
+    #[test]
+    fn errors_are_recovered_from() {
+        let source          = "x: int = )\ny: int = 2\nfunc f (a) -> int\nend\nwhile a\n  1 + )\n  break\nend\nz = 3\n";
+        let (program, errs) = parse_program(source);
+
+        assert_eq!(errs.iter().map(|e| e.span.line).collect::<Vec<_>>(), vec![1, 3, 6]);
+        assert_eq!(
+            dump::program(&program).without_spans().to_sexp().split_whitespace().collect::<Vec<_>>().join(" "),
+            "(program :items ((var :name \"y\" :type \"int\" :value (integer :value 2 :suffix nil)) \
+             (while :condition (identifier :name \"a\") :body (block :statements ((break)))) \
+             (expr :expr (assign :target (identifier :name \"z\") :value (integer :value 3 :suffix nil)))))",
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start:  usize,
+    pub end:    usize,
+    pub line:   usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start:  start,
+            end:    end,
+            line:   line,
+            column: column,
+        }
+    }
+
+    // smallest span covering both `self` and `other`
+    pub fn to(&self, other: Span) -> Span {
+        if other.start < self.start {
+            Span::new(other.start, self.end.max(other.end), other.line, other.column)
+        } else {
+            Span::new(self.start, self.end.max(other.end), self.line, self.column)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Spanned<T> {
+        Spanned {
+            node: node,
+            span: span,
+        }
+    }
+}
+
+// maps byte offsets of a source back onto 1-based lines and columns
+#[derive(Debug, Clone)]
+pub struct LineMap<'a> {
+    source:      &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineMap<'a> {
+    pub fn new(source: &'a str) -> LineMap<'a> {
+        let mut line_starts = vec![0];
+
+        for (i, c) in source.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+
+        LineMap {
+            source:      source,
+            line_starts: line_starts,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    pub fn line_of(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line)  => line + 1,
+            Err(line) => line,
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line  = self.line_of(offset);
+        let start = self.line_starts[line - 1];
+        let end   = offset.min(self.source.len());
+
+        (line, self.source[start..end].chars().count() + 1)
+    }
+
+    pub fn line(&self, line: usize) -> Option<&'a str> {
+        if line == 0 || line > self.line_starts.len() {
+            return None
+        }
+
+        let start = self.line_starts[line - 1];
+        let end   = match self.line_starts.get(line) {
+            Some(&next) => next - 1,
+            None        => self.source.len(),
+        };
+
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+}
@@ -0,0 +1,433 @@
+use parser::parser::{Program, Item, Function, Lambda, Statement, Expr, InterpolationPart};
+use parser::span::Spanned;
+
+// walks the AST, each `visit_` method defaults to visiting every child of its node so a pass only
+// overrides the nodes it cares about, calling the matching `walk_` function to carry on below them
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_item(&mut self, item: &Spanned<Item>) {
+        walk_item(self, item)
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function)
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) {
+        walk_lambda(self, lambda)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+        walk_expr(self, expr)
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for item in &program.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<V: Visitor + ?Sized>(visitor: &mut V, item: &Spanned<Item>) {
+    match item.node {
+        Item::Function(ref function)   => visitor.visit_function(function),
+        Item::Statement(ref statement) => visitor.visit_statement(statement),
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    visitor.visit_statement(&function.body)
+}
+
+pub fn walk_lambda<V: Visitor + ?Sized>(visitor: &mut V, lambda: &Lambda) {
+    visitor.visit_statement(&lambda.body)
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match *statement {
+        Statement::If(ref condition, ref body)     |
+        Statement::Unless(ref condition, ref body) |
+        Statement::While(ref condition, ref body)  |
+        Statement::Until(ref condition, ref body)  => {
+            visitor.visit_expr(condition);
+            visitor.visit_statement(body);
+        }
+
+        Statement::IfElse(ref condition, ref body, ref otherwise)     |
+        Statement::UnlessElse(ref condition, ref body, ref otherwise) => {
+            visitor.visit_expr(condition);
+            visitor.visit_statement(body);
+            visitor.visit_statement(otherwise);
+        }
+
+        Statement::For(_, ref iterable, ref body) => {
+            visitor.visit_expr(iterable);
+            visitor.visit_statement(body);
+        }
+
+        Statement::Var(_, _, Some(ref value)) => visitor.visit_expr(value),
+
+        Statement::Block(ref statements) => for statement in statements.iter() {
+            visitor.visit_statement(&statement.node);
+        },
+
+        Statement::Expr(ref expr)          |
+        Statement::ReturnWithVal(ref expr) => visitor.visit_expr(expr),
+
+        Statement::Var(_, _, None) |
+        Statement::Return          |
+        Statement::Break           |
+        Statement::Continue        => (),
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Spanned<Expr>) {
+    match expr.node {
+        Expr::FnCall(_, ref args) => for arg in args.iter() {
+            visitor.visit_expr(arg);
+        },
+
+        Expr::Interpolation(ref parts) => for part in parts {
+            if let InterpolationPart::Expr(ref expr) = *part {
+                visitor.visit_expr(expr);
+            }
+        },
+
+        // the member of a `Dot` is visited as an `Identifier` too
+        Expr::Dot(ref lhs, ref rhs)                   |
+        Expr::Index(ref lhs, ref rhs)                 |
+        Expr::Assignment(ref lhs, ref rhs)            |
+        Expr::CompoundAssignment(_, ref lhs, ref rhs) |
+        Expr::Binary(ref lhs, _, ref rhs)             |
+        Expr::Range(ref lhs, ref rhs)                 => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+
+        Expr::Unary(_, ref operand) => visitor.visit_expr(operand),
+
+        Expr::Lambda(ref lambda)           => visitor.visit_lambda(lambda),
+        Expr::Conditional(ref conditional) => visitor.visit_statement(conditional),
+
+        Expr::Integer(..) | Expr::Float(..) | Expr::Text(_) | Expr::Identifier(_) | Expr::True | Expr::False => (),
+    }
+}
+
+// like `Visitor`, but free to change the nodes it walks over
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Spanned<Item>) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function)
+    }
+
+    fn visit_lambda_mut(&mut self, lambda: &mut Lambda) {
+        walk_lambda_mut(self, lambda)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Spanned<Expr>) {
+        walk_expr_mut(self, expr)
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for item in &mut program.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut Spanned<Item>) {
+    match item.node {
+        Item::Function(ref mut function)   => visitor.visit_function_mut(function),
+        Item::Statement(ref mut statement) => visitor.visit_statement_mut(statement),
+    }
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    visitor.visit_statement_mut(&mut function.body)
+}
+
+pub fn walk_lambda_mut<V: VisitorMut + ?Sized>(visitor: &mut V, lambda: &mut Lambda) {
+    visitor.visit_statement_mut(&mut lambda.body)
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match *statement {
+        Statement::If(ref mut condition, ref mut body)     |
+        Statement::Unless(ref mut condition, ref mut body) |
+        Statement::While(ref mut condition, ref mut body)  |
+        Statement::Until(ref mut condition, ref mut body)  => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_statement_mut(body);
+        }
+
+        Statement::IfElse(ref mut condition, ref mut body, ref mut otherwise)     |
+        Statement::UnlessElse(ref mut condition, ref mut body, ref mut otherwise) => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_statement_mut(body);
+            visitor.visit_statement_mut(otherwise);
+        }
+
+        Statement::For(_, ref mut iterable, ref mut body) => {
+            visitor.visit_expr_mut(iterable);
+            visitor.visit_statement_mut(body);
+        }
+
+        Statement::Var(_, _, Some(ref mut value)) => visitor.visit_expr_mut(value),
+
+        Statement::Block(ref mut statements) => for statement in statements.iter_mut() {
+            visitor.visit_statement_mut(&mut statement.node);
+        },
+
+        Statement::Expr(ref mut expr)          |
+        Statement::ReturnWithVal(ref mut expr) => visitor.visit_expr_mut(expr),
+
+        Statement::Var(_, _, None) |
+        Statement::Return          |
+        Statement::Break           |
+        Statement::Continue        => (),
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Spanned<Expr>) {
+    match expr.node {
+        Expr::FnCall(_, ref mut args) => for arg in args.iter_mut() {
+            visitor.visit_expr_mut(arg);
+        },
+
+        Expr::Interpolation(ref mut parts) => for part in parts {
+            if let InterpolationPart::Expr(ref mut expr) = *part {
+                visitor.visit_expr_mut(expr);
+            }
+        },
+
+        Expr::Dot(ref mut lhs, ref mut rhs)                   |
+        Expr::Index(ref mut lhs, ref mut rhs)                 |
+        Expr::Assignment(ref mut lhs, ref mut rhs)            |
+        Expr::CompoundAssignment(_, ref mut lhs, ref mut rhs) |
+        Expr::Binary(ref mut lhs, _, ref mut rhs)             |
+        Expr::Range(ref mut lhs, ref mut rhs)                 => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        }
+
+        Expr::Unary(_, ref mut operand) => visitor.visit_expr_mut(operand),
+
+        Expr::Lambda(ref mut lambda)           => visitor.visit_lambda_mut(lambda),
+        Expr::Conditional(ref mut conditional) => visitor.visit_statement_mut(conditional),
+
+        Expr::Integer(..) | Expr::Float(..) | Expr::Text(_) | Expr::Identifier(_) | Expr::True | Expr::False => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parser::parser::parse_program;
+
+    use std::collections::BTreeSet;
+
+    // a statement or expression of every kind, nested in each place one can be
+    const SOURCE: &str = "
+func f (a: int) -> int
+    return a
+end
+
+func g () -> void
+    return
+end
+
+x: int = 1
+y: float = 1.5
+s: str = \"text\"
+t: str = \"x is {x}\"
+u: int
+x = f(x)
+x += 1
+v: int = -x + x * 2
+for i in 0..x
+    break
+end
+while x < 10
+    continue
+end
+until true
+end
+if false
+end
+unless x == 1
+end
+z: int = if x > 0
+    1
+else
+    2
+end
+w: int = unless x > 0
+    1
+else
+    2
+end
+h: func (int) -> int = lambda (n: int) -> int
+    return n + x
+end
+q: int = p.m
+r: int = items[x]
+";
+
+    fn statement_kind(statement: &Statement) -> &'static str {
+        match *statement {
+            Statement::If(..)           => "if",
+            Statement::IfElse(..)       => "if_else",
+            Statement::Unless(..)       => "unless",
+            Statement::UnlessElse(..)   => "unless_else",
+            Statement::While(..)        => "while",
+            Statement::Until(..)        => "until",
+            Statement::For(..)          => "for",
+            Statement::Break            => "break",
+            Statement::Continue         => "continue",
+            Statement::Var(..)          => "var",
+            Statement::Block(_)         => "block",
+            Statement::Expr(_)          => "expr",
+            Statement::Return           => "return",
+            Statement::ReturnWithVal(_) => "return_with_value",
+        }
+    }
+
+    fn expr_kind(expr: &Expr) -> &'static str {
+        match *expr {
+            Expr::Integer(..)            => "integer",
+            Expr::Float(..)              => "float",
+            Expr::Text(_)                => "text",
+            Expr::Interpolation(_)       => "interpolation",
+            Expr::Identifier(_)          => "identifier",
+            Expr::FnCall(..)             => "call",
+            Expr::Dot(..)                => "dot",
+            Expr::Index(..)              => "index",
+            Expr::Assignment(..)         => "assignment",
+            Expr::CompoundAssignment(..) => "compound_assignment",
+            Expr::Binary(..)             => "binary",
+            Expr::Unary(..)              => "unary",
+            Expr::Range(..)              => "range",
+            Expr::Lambda(_)              => "lambda",
+            Expr::Conditional(_)         => "conditional",
+            Expr::True                   => "true",
+            Expr::False                  => "false",
+        }
+    }
+
+    // upper-cases every name used, noting the kind of each node it passes
+    #[derive(Default)]
+    struct Rename {
+        visited: BTreeSet<&'static str>,
+    }
+
+    impl VisitorMut for Rename {
+        fn visit_statement_mut(&mut self, statement: &mut Statement) {
+            self.visited.insert(statement_kind(statement));
+
+            walk_statement_mut(self, statement)
+        }
+
+        fn visit_expr_mut(&mut self, expr: &mut Spanned<Expr>) {
+            self.visited.insert(expr_kind(&expr.node));
+
+            match expr.node {
+                Expr::Identifier(ref mut name) | Expr::FnCall(ref mut name, _) => *name = name.to_uppercase(),
+                _                                                              => (),
+            }
+
+            walk_expr_mut(self, expr)
+        }
+    }
+
+    // every name used, and the kind of every node passed
+    #[derive(Default)]
+    struct Names {
+        names:   Vec<String>,
+        visited: BTreeSet<&'static str>,
+    }
+
+    impl Visitor for Names {
+        fn visit_statement(&mut self, statement: &Statement) {
+            self.visited.insert(statement_kind(statement));
+
+            walk_statement(self, statement)
+        }
+
+        fn visit_expr(&mut self, expr: &Spanned<Expr>) {
+            self.visited.insert(expr_kind(&expr.node));
+
+            match expr.node {
+                Expr::Identifier(ref name) | Expr::FnCall(ref name, _) => self.names.push(name.clone()),
+                _                                                      => (),
+            }
+
+            walk_expr(self, expr)
+        }
+    }
+
+    fn every_kind() -> BTreeSet<&'static str> {
+        [
+            "if", "if_else", "unless", "unless_else", "while", "until", "for", "break", "continue", "var",
+            "block", "expr", "return", "return_with_value",
+
+            "integer", "float", "text", "interpolation", "identifier", "call", "dot", "index", "assignment",
+            "compound_assignment", "binary", "unary", "range", "lambda", "conditional", "true", "false",
+        ].iter().cloned().collect()
+    }
+
+    #[test]
+    fn every_node_is_visited() {
+        let (program, errors) = parse_program(SOURCE);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let mut names = Names::default();
+
+        names.visit_program(&program);
+
+        assert_eq!(names.visited, every_kind());
+        assert_eq!(names.names, vec![
+            "a", "x", "x", "f", "x", "x", "x", "x", "x", "x", "x", "x", "x", "n", "x", "p", "m", "items", "x",
+        ]);
+    }
+
+    #[test]
+    fn every_name_is_renamed() {
+        let (mut program, errors) = parse_program(SOURCE);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let mut rename = Rename::default();
+
+        rename.visit_program_mut(&mut program);
+
+        assert_eq!(rename.visited, every_kind());
+
+        let mut names = Names::default();
+
+        names.visit_program(&program);
+
+        // including the ones in interpolations, lambda bodies, dots, indices and calls
+        assert_eq!(names.names.len(), 19);
+        assert!(names.names.iter().all(|name| *name == name.to_uppercase()), "{:?}", names.names);
+    }
+}
@@ -1,23 +1,198 @@
 use vm::LLVMRef;
 use vm::context::Context;
+use vm::function::Function;
+use vm::llvm_type::type_name;
 use vm::value::Value;
 
-use vm::libc::c_char;
+use vm::libc::{c_char, c_uint};
+use vm::llvm::{LLVMIntPredicate, LLVMRealPredicate, LLVMTypeKind};
 use vm::llvm::core::{
-    LLVMBuildAdd,
+    LLVMAddCase,
+    LLVMAddIncoming,
+    LLVMBasicBlockAsValue,
+    LLVMBuildAlloca,
+    LLVMBuildArrayAlloca,
+    LLVMBuildArrayMalloc,
+    LLVMBuildBr,
+    LLVMBuildCall,
+    LLVMBuildCondBr,
+    LLVMBuildExtractValue,
+    LLVMBuildFCmp,
+    LLVMBuildFree,
+    LLVMBuildGlobalStringPtr,
+    LLVMBuildGEP,
+    LLVMBuildICmp,
+    LLVMBuildInBoundsGEP,
+    LLVMBuildInsertValue,
+    LLVMBuildLoad,
+    LLVMBuildMalloc,
+    LLVMBuildPhi,
     LLVMBuildRet,
     LLVMBuildRetVoid,
+    LLVMBuildSelect,
+    LLVMBuildStore,
+    LLVMBuildStructGEP,
+    LLVMBuildSwitch,
+    LLVMBuildUnreachable,
+    LLVMCountParamTypes,
     LLVMCreateBuilderInContext,
+    LLVMDeleteBasicBlock,
     LLVMDisposeBuilder,
+    LLVMGetBasicBlockParent,
+    LLVMGetBasicBlockTerminator,
+    LLVMGetElementType,
+    LLVMGetFirstInstruction,
+    LLVMGetInsertBlock,
+    LLVMGetNextBasicBlock,
+    LLVMGetNextInstruction,
+    LLVMGetParamTypes,
+    LLVMGetPreviousBasicBlock,
+    LLVMGetTypeKind,
+    LLVMGetValueName,
+    LLVMIsAAllocaInst,
+    LLVMIsFunctionVarArg,
+    LLVMMoveBasicBlockAfter,
+    LLVMMoveBasicBlockBefore,
     LLVMPositionBuilderAtEnd,
+    LLVMPositionBuilderBefore,
+    LLVMSetTailCall,
 };
 
 use vm::llvm::prelude::{
     LLVMBasicBlockRef,
     LLVMBuilderRef,
+    LLVMTypeRef,
+    LLVMValueRef,
 };
 
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+macro_rules! bind_binary_op {
+    ($LLVM_name:ident => $name:ident) => (
+        pub fn $name(&mut self, lhs: Value, rhs: Value, name: &str) -> Value {
+            use vm::llvm::core::$LLVM_name;
+
+            let name = CString::new(name).unwrap();
+
+            Value::from_ref(unsafe {
+                $LLVM_name(
+                    self.to_ref(),
+                    lhs.to_ref(),
+                    rhs.to_ref(),
+                    name.as_ptr() as *const c_char,
+                )
+            })
+        }
+    )
+}
+
+macro_rules! bind_unary_op {
+    ($LLVM_name:ident => $name:ident) => (
+        pub fn $name(&mut self, v: Value, name: &str) -> Value {
+            use vm::llvm::core::$LLVM_name;
+
+            let name = CString::new(name).unwrap();
+
+            Value::from_ref(unsafe {
+                $LLVM_name(self.to_ref(), v.to_ref(), name.as_ptr() as *const c_char)
+            })
+        }
+    )
+}
+
+macro_rules! bind_cast {
+    ($LLVM_name:ident => $name:ident) => (
+        pub fn $name(&mut self, v: Value, ty: LLVMTypeRef, name: &str) -> Value {
+            use vm::llvm::core::$LLVM_name;
+
+            let name = CString::new(name).unwrap();
+
+            Value::from_ref(unsafe {
+                $LLVM_name(self.to_ref(), v.to_ref(), ty, name.as_ptr() as *const c_char)
+            })
+        }
+    )
+}
+
+// how `icmp` compares two integers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntPredicate {
+    Equal,
+    NotEqual,
+    SignedLess,
+    SignedLessEqual,
+    SignedGreater,
+    SignedGreaterEqual,
+    UnsignedLess,
+    UnsignedLessEqual,
+    UnsignedGreater,
+    UnsignedGreaterEqual,
+}
+
+impl IntPredicate {
+    fn to_llvm(self) -> LLVMIntPredicate {
+        match self {
+            IntPredicate::Equal                => LLVMIntPredicate::LLVMIntEQ,
+            IntPredicate::NotEqual             => LLVMIntPredicate::LLVMIntNE,
+            IntPredicate::SignedLess           => LLVMIntPredicate::LLVMIntSLT,
+            IntPredicate::SignedLessEqual      => LLVMIntPredicate::LLVMIntSLE,
+            IntPredicate::SignedGreater        => LLVMIntPredicate::LLVMIntSGT,
+            IntPredicate::SignedGreaterEqual   => LLVMIntPredicate::LLVMIntSGE,
+            IntPredicate::UnsignedLess         => LLVMIntPredicate::LLVMIntULT,
+            IntPredicate::UnsignedLessEqual    => LLVMIntPredicate::LLVMIntULE,
+            IntPredicate::UnsignedGreater      => LLVMIntPredicate::LLVMIntUGT,
+            IntPredicate::UnsignedGreaterEqual => LLVMIntPredicate::LLVMIntUGE,
+        }
+    }
+}
+
+// how `fcmp` compares two floats. ordered comparisons are false when either side is NaN, unordered
+// ones true. `False` and `True` don't look at their operands at all
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RealPredicate {
+    False,
+    OrderedEqual,
+    OrderedNotEqual,
+    OrderedLess,
+    OrderedLessEqual,
+    OrderedGreater,
+    OrderedGreaterEqual,
+    // neither side is NaN
+    Ordered,
+    // either side is NaN
+    Unordered,
+    UnorderedEqual,
+    UnorderedNotEqual,
+    UnorderedLess,
+    UnorderedLessEqual,
+    UnorderedGreater,
+    UnorderedGreaterEqual,
+    True,
+}
+
+impl RealPredicate {
+    fn to_llvm(self) -> LLVMRealPredicate {
+        match self {
+            RealPredicate::False                 => LLVMRealPredicate::LLVMRealPredicateFalse,
+            RealPredicate::OrderedEqual          => LLVMRealPredicate::LLVMRealOEQ,
+            RealPredicate::OrderedNotEqual       => LLVMRealPredicate::LLVMRealONE,
+            RealPredicate::OrderedLess           => LLVMRealPredicate::LLVMRealOLT,
+            RealPredicate::OrderedLessEqual      => LLVMRealPredicate::LLVMRealOLE,
+            RealPredicate::OrderedGreater        => LLVMRealPredicate::LLVMRealOGT,
+            RealPredicate::OrderedGreaterEqual   => LLVMRealPredicate::LLVMRealOGE,
+            RealPredicate::Ordered               => LLVMRealPredicate::LLVMRealORD,
+            RealPredicate::Unordered             => LLVMRealPredicate::LLVMRealUNO,
+            RealPredicate::UnorderedEqual        => LLVMRealPredicate::LLVMRealUEQ,
+            RealPredicate::UnorderedNotEqual     => LLVMRealPredicate::LLVMRealUNE,
+            RealPredicate::UnorderedLess         => LLVMRealPredicate::LLVMRealULT,
+            RealPredicate::UnorderedLessEqual    => LLVMRealPredicate::LLVMRealULE,
+            RealPredicate::UnorderedGreater      => LLVMRealPredicate::LLVMRealUGT,
+            RealPredicate::UnorderedGreaterEqual => LLVMRealPredicate::LLVMRealUGE,
+            RealPredicate::True                  => LLVMRealPredicate::LLVMRealPredicateTrue,
+        }
+    }
+}
 
 pub struct Builder {
     builder: LLVMBuilderRef,
@@ -40,6 +215,19 @@ impl Builder {
         }
     }
 
+    pub fn move_before(&mut self, instruction: Value) {
+        unsafe {
+            LLVMPositionBuilderBefore(self.to_ref(), instruction.to_ref());
+        }
+    }
+
+    // the block instructions are currently added to
+    pub fn insert_block(&self) -> BasicBlock {
+        BasicBlock::from_ref(unsafe {
+            LLVMGetInsertBlock(self.to_ref())
+        })
+    }
+
     pub fn return_void(&mut self) -> Value {
         Value::from_ref(unsafe {
             LLVMBuildRetVoid(self.to_ref())
@@ -52,17 +240,335 @@ impl Builder {
         })
     }
 
-    pub fn add(&mut self, lhs: Value, rhs: Value, name: &str) -> Value {
+    bind_binary_op!(LLVMBuildAdd => add);
+    bind_binary_op!(LLVMBuildSub => sub);
+    bind_binary_op!(LLVMBuildMul => mul);
+    bind_binary_op!(LLVMBuildSDiv => sdiv);
+    bind_binary_op!(LLVMBuildUDiv => udiv);
+    bind_binary_op!(LLVMBuildSRem => srem);
+    bind_binary_op!(LLVMBuildURem => urem);
+
+    bind_binary_op!(LLVMBuildFAdd => fadd);
+    bind_binary_op!(LLVMBuildFSub => fsub);
+    bind_binary_op!(LLVMBuildFMul => fmul);
+    bind_binary_op!(LLVMBuildFDiv => fdiv);
+    bind_binary_op!(LLVMBuildFRem => frem);
+
+    bind_binary_op!(LLVMBuildAnd => and);
+    bind_binary_op!(LLVMBuildOr => or);
+    bind_binary_op!(LLVMBuildXor => xor);
+
+    // `lshr` fills in zeroes from the left, `ashr` copies the sign bit
+    bind_binary_op!(LLVMBuildShl => shl);
+    bind_binary_op!(LLVMBuildLShr => lshr);
+    bind_binary_op!(LLVMBuildAShr => ashr);
+
+    bind_unary_op!(LLVMBuildNeg => neg);
+    bind_unary_op!(LLVMBuildFNeg => fneg);
+    bind_unary_op!(LLVMBuildNot => not);
+
+    // `sext` copies the sign bit into the new bits, `zext` fills them with zeroes
+    bind_cast!(LLVMBuildSExt => sext);
+    bind_cast!(LLVMBuildZExt => zext);
+    bind_cast!(LLVMBuildFPExt => fpext);
+    bind_cast!(LLVMBuildBitCast => bitcast);
+
+    pub fn select(&mut self, condition: Value, then: Value, otherwise: Value, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildSelect(self.to_ref(), condition.to_ref(), then.to_ref(), otherwise.to_ref(), name.as_ptr() as *const c_char)
+        })
+    }
+
+    // a copy of a struct or array with the element at `index` replaced
+    pub fn insert_value(&mut self, aggregate: Value, element: Value, index: u32, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildInsertValue(self.to_ref(), aggregate.to_ref(), element.to_ref(), index as c_uint, name.as_ptr() as *const c_char)
+        })
+    }
+
+    pub fn extract_value(&mut self, aggregate: Value, index: u32, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildExtractValue(self.to_ref(), aggregate.to_ref(), index as c_uint, name.as_ptr() as *const c_char)
+        })
+    }
+
+    // a constant, nul-terminated string in a new global, as an `i8*` to its first character. text after
+    // a nul is left out, nothing reading it as a C string would get that far
+    pub fn global_string(&mut self, text: &str, name: &str) -> Value {
+        let text = CString::new(text.split('\0').next().unwrap()).unwrap();
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildGlobalStringPtr(self.to_ref(), text.as_ptr() as *const c_char, name.as_ptr() as *const c_char)
+        })
+    }
+
+    pub fn icmp(&mut self, predicate: IntPredicate, lhs: Value, rhs: Value, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildICmp(self.to_ref(), predicate.to_llvm(), lhs.to_ref(), rhs.to_ref(), name.as_ptr() as *const c_char)
+        })
+    }
+
+    pub fn fcmp(&mut self, predicate: RealPredicate, lhs: Value, rhs: Value, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildFCmp(self.to_ref(), predicate.to_llvm(), lhs.to_ref(), rhs.to_ref(), name.as_ptr() as *const c_char)
+        })
+    }
+
+    pub fn br(&mut self, destination: BasicBlock) -> Value {
+        Value::from_ref(unsafe {
+            LLVMBuildBr(self.to_ref(), destination.to_ref())
+        })
+    }
+
+    pub fn cond_br(&mut self, condition: Value, then: BasicBlock, otherwise: BasicBlock) -> Value {
+        Value::from_ref(unsafe {
+            LLVMBuildCondBr(self.to_ref(), condition.to_ref(), then.to_ref(), otherwise.to_ref())
+        })
+    }
+
+    // jumps to the block of the first case equal to `v`, or to `default` when none is
+    pub fn switch(&mut self, v: Value, default: BasicBlock, cases: &[(Value, BasicBlock)]) -> Value {
+        unsafe {
+            let switch = LLVMBuildSwitch(self.to_ref(), v.to_ref(), default.to_ref(), cases.len() as c_uint);
+
+            for &(case, block) in cases {
+                LLVMAddCase(switch, case.to_ref(), block.to_ref());
+            }
+
+            Value::from_ref(switch)
+        }
+    }
+
+    // takes the value paired with whichever block was just come from. every predecessor
+    // of the current block needs exactly one, and phis have to come before anything else in it
+    pub fn phi(&mut self, ty: LLVMTypeRef, incoming: &[(Value, BasicBlock)], name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        let mut values = incoming.iter().map(|&(v, _)| v.to_ref()).collect::<Vec<LLVMValueRef>>();
+        let mut blocks = incoming.iter().map(|&(_, b)| b.to_ref()).collect::<Vec<LLVMBasicBlockRef>>();
+
+        unsafe {
+            let phi = LLVMBuildPhi(self.to_ref(), ty, name.as_ptr() as *const c_char);
+
+            LLVMAddIncoming(phi, values.as_mut_ptr(), blocks.as_mut_ptr(), incoming.len() as c_uint);
+
+            Value::from_ref(phi)
+        }
+    }
+
+    // ends a block that can never be reached
+    pub fn unreachable(&mut self) -> Value {
+        Value::from_ref(unsafe {
+            LLVMBuildUnreachable(self.to_ref())
+        })
+    }
+
+    // a stack slot for one `ty`, returning a pointer to it
+    pub fn alloca(&mut self, ty: LLVMTypeRef, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildAlloca(self.to_ref(), ty, name.as_ptr() as *const c_char)
+        })
+    }
+
+    // a stack slot for `count` of `ty` in a row
+    pub fn array_alloca(&mut self, ty: LLVMTypeRef, count: Value, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildArrayAlloca(self.to_ref(), ty, count.to_ref(), name.as_ptr() as *const c_char)
+        })
+    }
+
+    // an `alloca` among the others at the top of the function's entry block, wherever the builder is.
+    // only slots there are promoted to registers by mem2reg, and they're made once however often
+    // the code declaring them runs
+    pub fn entry_alloca(&mut self, ty: LLVMTypeRef, name: &str) -> Value {
+        let current = self.insert_block();
+        let entry   = current.parent().entry_block();
+
+        let mut instruction = entry.first_instruction();
+
+        while let Some(i) = instruction {
+            if unsafe { LLVMIsAAllocaInst(i.to_ref()) }.is_null() {
+                break
+            }
+
+            instruction = next_instruction(i);
+        }
+
+        match instruction {
+            Some(i) => self.move_before(i),
+            None    => self.move_to_end(entry),
+        }
+
+        let slot = self.alloca(ty, name);
+
+        self.move_to_end(current);
+
+        slot
+    }
+
+    pub fn load(&mut self, pointer: Value, name: &str) -> Value {
         let name = CString::new(name).unwrap();
 
         Value::from_ref(unsafe {
-            LLVMBuildAdd(
+            LLVMBuildLoad(self.to_ref(), pointer.to_ref(), name.as_ptr() as *const c_char)
+        })
+    }
+
+    pub fn store(&mut self, v: Value, pointer: Value) -> Value {
+        Value::from_ref(unsafe {
+            LLVMBuildStore(self.to_ref(), v.to_ref(), pointer.to_ref())
+        })
+    }
+
+    // the address of an element, one index per level of pointer, array or struct being stepped into
+    pub fn gep(&mut self, pointer: Value, indices: &[Value], name: &str) -> Value {
+        let name        = CString::new(name).unwrap();
+        let mut indices = indices.iter().map(|i| i.to_ref()).collect::<Vec<LLVMValueRef>>();
+
+        Value::from_ref(unsafe {
+            LLVMBuildGEP(self.to_ref(), pointer.to_ref(), indices.as_mut_ptr(), indices.len() as c_uint, name.as_ptr() as *const c_char)
+        })
+    }
+
+    // like `gep`, promising the address stays inside the object `pointer` points to
+    pub fn inbounds_gep(&mut self, pointer: Value, indices: &[Value], name: &str) -> Value {
+        let name        = CString::new(name).unwrap();
+        let mut indices = indices.iter().map(|i| i.to_ref()).collect::<Vec<LLVMValueRef>>();
+
+        Value::from_ref(unsafe {
+            LLVMBuildInBoundsGEP(self.to_ref(), pointer.to_ref(), indices.as_mut_ptr(), indices.len() as c_uint, name.as_ptr() as *const c_char)
+        })
+    }
+
+    // the address of field `index` of the struct `pointer` points to
+    pub fn struct_gep(&mut self, pointer: Value, index: u32, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildStructGEP(self.to_ref(), pointer.to_ref(), index as c_uint, name.as_ptr() as *const c_char)
+        })
+    }
+
+    // heap memory for one `ty`, from the C runtime's `malloc`
+    pub fn malloc(&mut self, ty: LLVMTypeRef, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildMalloc(self.to_ref(), ty, name.as_ptr() as *const c_char)
+        })
+    }
+
+    pub fn array_malloc(&mut self, ty: LLVMTypeRef, count: Value, name: &str) -> Value {
+        let name = CString::new(name).unwrap();
+
+        Value::from_ref(unsafe {
+            LLVMBuildArrayMalloc(self.to_ref(), ty, count.to_ref(), name.as_ptr() as *const c_char)
+        })
+    }
+
+    pub fn free(&mut self, pointer: Value) -> Value {
+        Value::from_ref(unsafe {
+            LLVMBuildFree(self.to_ref(), pointer.to_ref())
+        })
+    }
+
+    // calls returning `void` must be left unnamed. LLVM only checks the arguments when the module is
+    // verified, so ones that don't fit the function's parameters are refused here instead
+    pub fn call(&mut self, function: Function, args: &[Value], name: &str) -> Result<Value, String> {
+        let params = (0 .. function.arity()).map(|i| function.param(i).ty()).collect::<Vec<LLVMTypeRef>>();
+
+        self.build_call(function.to_ref(), &format!("`{}`", function.name()), &params, function.is_variadic(), args, name)
+    }
+
+    // a call through a pointer to a function, e.g. the code of a closure, checked the same way
+    pub fn call_pointer(&mut self, pointer: Value, args: &[Value], name: &str) -> Result<Value, String> {
+        let is_function_pointer = unsafe {
+            match LLVMGetTypeKind(pointer.ty()) {
+                LLVMTypeKind::LLVMPointerTypeKind => matches!(LLVMGetTypeKind(LLVMGetElementType(pointer.ty())), LLVMTypeKind::LLVMFunctionTypeKind),
+                _                                 => false,
+            }
+        };
+
+        if !is_function_pointer {
+            return Err(format!("`{}` is called, but isn't a pointer to a function", type_name(pointer.ty())))
+        }
+
+        // a pointer has no parameters to count, so they're read off the type it points to
+        let (params, variadic) = unsafe {
+            let ty         = LLVMGetElementType(pointer.ty());
+            let mut params = vec![ptr::null_mut(); LLVMCountParamTypes(ty) as usize];
+
+            LLVMGetParamTypes(ty, params.as_mut_ptr());
+
+            (params, LLVMIsFunctionVarArg(ty) != 0)
+        };
+
+        self.build_call(pointer.to_ref(), "the function pointer", &params, variadic, args, name)
+    }
+
+    fn build_call(&mut self, callee: LLVMValueRef, described: &str, params: &[LLVMTypeRef], variadic: bool, args: &[Value], name: &str) -> Result<Value, String> {
+        if args.len() < params.len() || args.len() > params.len() && !variadic {
+            return Err(format!("{} takes {} arguments, but was called with {}", described, params.len(), args.len()))
+        }
+
+        // variadic arguments can be anything
+        for (i, (arg, &param)) in args.iter().zip(params).enumerate() {
+            if arg.ty() != param {
+                return Err(format!(
+                    "argument {} of {} should be `{}`, but is `{}`",
+                    i + 1, described, type_name(param), type_name(arg.ty()),
+                ))
+            }
+        }
+
+        let name     = CString::new(name).unwrap();
+        let mut args = args.iter().map(|a| a.to_ref()).collect::<Vec<LLVMValueRef>>();
+
+        Ok(Value::from_ref(unsafe {
+            LLVMBuildCall(
                 self.to_ref(),
-                lhs.to_ref(),
-                rhs.to_ref(),
+                callee,
+                args.as_mut_ptr(),
+                args.len() as c_uint,
                 name.as_ptr() as *const c_char,
             )
-        })
+        }))
+    }
+
+    // a call whose result is returned right away, which lets LLVM reuse the caller's stack frame
+    pub fn tail_call(&mut self, function: Function, args: &[Value], name: &str) -> Result<Value, String> {
+        let call = self.call(function, args, name)?;
+
+        unsafe {
+            LLVMSetTailCall(call.to_ref(), 1);
+        }
+
+        Ok(call)
+    }
+
+    pub fn tail_call_pointer(&mut self, pointer: Value, args: &[Value], name: &str) -> Result<Value, String> {
+        let call = self.call_pointer(pointer, args, name)?;
+
+        unsafe {
+            LLVMSetTailCall(call.to_ref(), 1);
+        }
+
+        Ok(call)
     }
 }
 
@@ -82,7 +588,7 @@ impl LLVMRef<LLVMBuilderRef> for Builder {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BasicBlock {
     block: LLVMBasicBlockRef,
 }
@@ -93,10 +599,520 @@ impl BasicBlock {
             block: block_ref,
         }
     }
+
+    fn from_nullable(block_ref: LLVMBasicBlockRef) -> Option<BasicBlock> {
+        if block_ref.is_null() {
+            None
+        } else {
+            Some(BasicBlock::from_ref(block_ref))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        unsafe {
+            CStr::from_ptr(LLVMGetValueName(LLVMBasicBlockAsValue(self.to_ref()))).to_string_lossy().into_owned()
+        }
+    }
+
+    pub fn parent(&self) -> Function {
+        Function::from_ref(unsafe {
+            LLVMGetBasicBlockParent(self.to_ref())
+        })
+    }
+
+    // the return, branch or such the block ends in, if it's been added yet
+    pub fn terminator(&self) -> Option<Value> {
+        let terminator = unsafe {
+            LLVMGetBasicBlockTerminator(self.to_ref())
+        };
+
+        if terminator.is_null() {
+            None
+        } else {
+            Some(Value::from_ref(terminator))
+        }
+    }
+
+    pub fn first_instruction(&self) -> Option<Value> {
+        let instruction = unsafe {
+            LLVMGetFirstInstruction(self.to_ref())
+        };
+
+        if instruction.is_null() {
+            None
+        } else {
+            Some(Value::from_ref(instruction))
+        }
+    }
+
+    pub fn next(&self) -> Option<BasicBlock> {
+        BasicBlock::from_nullable(unsafe {
+            LLVMGetNextBasicBlock(self.to_ref())
+        })
+    }
+
+    pub fn previous(&self) -> Option<BasicBlock> {
+        BasicBlock::from_nullable(unsafe {
+            LLVMGetPreviousBasicBlock(self.to_ref())
+        })
+    }
+
+    // moving only changes where the block is laid out, not how control reaches it
+    pub fn move_before(&self, other: BasicBlock) {
+        unsafe {
+            LLVMMoveBasicBlockBefore(self.to_ref(), other.to_ref());
+        }
+    }
+
+    pub fn move_after(&self, other: BasicBlock) {
+        unsafe {
+            LLVMMoveBasicBlockAfter(self.to_ref(), other.to_ref());
+        }
+    }
+
+    // removes the block from its function along with its instructions, leaving any copies of it dangling
+    pub unsafe fn delete(self) {
+        LLVMDeleteBasicBlock(self.to_ref());
+    }
 }
 
 impl LLVMRef<LLVMBasicBlockRef> for BasicBlock {
     fn to_ref(&self) -> LLVMBasicBlockRef {
         self.block
     }
-}
\ No newline at end of file
+}
+
+fn next_instruction(instruction: Value) -> Option<Value> {
+    let next = unsafe {
+        LLVMGetNextInstruction(instruction.to_ref())
+    };
+
+    if next.is_null() {
+        None
+    } else {
+        Some(Value::from_ref(next))
+    }
+}
+
+// the blocks of a function in layout order, see `Function::basic_blocks`
+pub struct BasicBlocks {
+    next: Option<BasicBlock>,
+}
+
+impl BasicBlocks {
+    pub fn new(first: LLVMBasicBlockRef) -> BasicBlocks {
+        BasicBlocks {
+            next: BasicBlock::from_nullable(first),
+        }
+    }
+}
+
+impl Iterator for BasicBlocks {
+    type Item = BasicBlock;
+
+    fn next(&mut self) -> Option<BasicBlock> {
+        let block = self.next?;
+
+        self.next = block.next();
+
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vm::llvm_type::{int1_type, int8_type, int32_type, int64_type, float_type, double_type, pointer_type, VMRepresentation};
+    use vm::module::Module;
+
+    // builds `test(a, b)` returning what `build` makes of its parameters, and checks the module is valid
+    fn check<F>(params: fn(&Context) -> LLVMTypeRef, ret: fn(&Context) -> LLVMTypeRef, opcode: &str, build: F)
+        where F: Fn(&mut Builder, Value, Value) -> Value
+    {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [params(&context), params(&context)], ret(&context));
+        let mut builder = Builder::new(&context);
+
+        builder.move_to_end(function.new_basic_block("entry"));
+
+        let result = build(&mut builder, function.param(0), function.param(1));
+
+        builder.return_value(result);
+
+        assert_eq!(module.verify(), Ok(()));
+        assert!(module.to_string().contains(opcode), "no `{}` in\n{}", opcode, module);
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        check(int32_type, int32_type, "add", |b, x, y| b.add(x, y, "add"));
+        check(int32_type, int32_type, "sub", |b, x, y| b.sub(x, y, "sub"));
+        check(int32_type, int32_type, "mul", |b, x, y| b.mul(x, y, "mul"));
+        check(int32_type, int32_type, "sdiv", |b, x, y| b.sdiv(x, y, "sdiv"));
+        check(int32_type, int32_type, "udiv", |b, x, y| b.udiv(x, y, "udiv"));
+        check(int32_type, int32_type, "srem", |b, x, y| b.srem(x, y, "srem"));
+        check(int32_type, int32_type, "urem", |b, x, y| b.urem(x, y, "urem"));
+        check(int32_type, int32_type, "sub", |b, x, _| b.neg(x, "neg"));
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        check(double_type, double_type, "fadd", |b, x, y| b.fadd(x, y, "fadd"));
+        check(double_type, double_type, "fsub", |b, x, y| b.fsub(x, y, "fsub"));
+        check(double_type, double_type, "fmul", |b, x, y| b.fmul(x, y, "fmul"));
+        check(double_type, double_type, "fdiv", |b, x, y| b.fdiv(x, y, "fdiv"));
+        check(double_type, double_type, "frem", |b, x, y| b.frem(x, y, "frem"));
+        check(double_type, double_type, "fneg", |b, x, _| b.fneg(x, "fneg"));
+    }
+
+    #[test]
+    fn logic_and_shifts() {
+        check(int32_type, int32_type, "and", |b, x, y| b.and(x, y, "and"));
+        check(int32_type, int32_type, "or", |b, x, y| b.or(x, y, "or"));
+        check(int32_type, int32_type, "xor", |b, x, y| b.xor(x, y, "xor"));
+        check(int32_type, int32_type, "shl", |b, x, y| b.shl(x, y, "shl"));
+        check(int32_type, int32_type, "lshr", |b, x, y| b.lshr(x, y, "lshr"));
+        check(int32_type, int32_type, "ashr", |b, x, y| b.ashr(x, y, "ashr"));
+        check(int32_type, int32_type, "xor", |b, x, _| b.not(x, "not"));
+    }
+
+    #[test]
+    fn comparisons() {
+        let int_predicates = [
+            (IntPredicate::Equal,                "eq"),
+            (IntPredicate::NotEqual,             "ne"),
+            (IntPredicate::SignedLess,           "slt"),
+            (IntPredicate::SignedLessEqual,      "sle"),
+            (IntPredicate::SignedGreater,        "sgt"),
+            (IntPredicate::SignedGreaterEqual,   "sge"),
+            (IntPredicate::UnsignedLess,         "ult"),
+            (IntPredicate::UnsignedLessEqual,    "ule"),
+            (IntPredicate::UnsignedGreater,      "ugt"),
+            (IntPredicate::UnsignedGreaterEqual, "uge"),
+        ];
+
+        let real_predicates = [
+            (RealPredicate::False,                 "false"),
+            (RealPredicate::OrderedEqual,          "oeq"),
+            (RealPredicate::OrderedNotEqual,       "one"),
+            (RealPredicate::OrderedLess,           "olt"),
+            (RealPredicate::OrderedLessEqual,      "ole"),
+            (RealPredicate::OrderedGreater,        "ogt"),
+            (RealPredicate::OrderedGreaterEqual,   "oge"),
+            (RealPredicate::Ordered,               "ord"),
+            (RealPredicate::Unordered,             "uno"),
+            (RealPredicate::UnorderedEqual,        "ueq"),
+            (RealPredicate::UnorderedNotEqual,     "une"),
+            (RealPredicate::UnorderedLess,         "ult"),
+            (RealPredicate::UnorderedLessEqual,    "ule"),
+            (RealPredicate::UnorderedGreater,      "ugt"),
+            (RealPredicate::UnorderedGreaterEqual, "uge"),
+            (RealPredicate::True,                  "true"),
+        ];
+
+        for &(predicate, mnemonic) in &int_predicates {
+            check(int32_type, int1_type, &format!("icmp {} i32", mnemonic), |b, x, y| b.icmp(predicate, x, y, "icmp"));
+        }
+
+        for &(predicate, mnemonic) in &real_predicates {
+            check(double_type, int1_type, &format!("fcmp {} double", mnemonic), |b, x, y| b.fcmp(predicate, x, y, "fcmp"));
+        }
+    }
+
+    #[test]
+    fn casts_and_select() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int32_type(&context), float_type(&context)], double_type(&context));
+        let mut builder = Builder::new(&context);
+
+        builder.move_to_end(function.new_basic_block("entry"));
+
+        let wide     = builder.sext(function.param(0), int64_type(&context), "wide");
+        let unsigned = builder.zext(function.param(0), int64_type(&context), "unsigned");
+        let bits     = builder.bitcast(wide, double_type(&context), "bits");
+        let real     = builder.fpext(function.param(1), double_type(&context), "real");
+        let less     = builder.icmp(IntPredicate::SignedLess, wide, unsigned, "less");
+        let chosen   = builder.select(less, bits, real, "chosen");
+
+        builder.return_value(chosen);
+
+        assert_eq!(module.verify(), Ok(()));
+
+        let ir = module.to_string();
+
+        for instruction in &[
+            "%wide = sext i32 %0 to i64",
+            "%unsigned = zext i32 %0 to i64",
+            "%bits = bitcast i64 %wide to double",
+            "%real = fpext float %1 to double",
+            "%chosen = select i1 %less, double %bits, double %real",
+        ] {
+            assert!(ir.contains(instruction), "no `{}` in\n{}", instruction, ir);
+        }
+    }
+
+    #[test]
+    fn calls_check_their_arguments() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int32_type(&context)], int32_type(&context));
+        let mut builder = Builder::new(&context);
+
+        builder.move_to_end(function.new_basic_block("entry"));
+
+        let param = function.param(0);
+
+        assert!(builder.call(function, &[], "call").is_err());
+        assert!(builder.call(function, &[param, param], "call").is_err());
+        assert!(builder.call(function, &[1.0f64.to_representation(&context)], "call").is_err());
+
+        let call = builder.tail_call(function, &[param], "call").unwrap();
+
+        builder.return_value(call);
+
+        assert_eq!(module.verify(), Ok(()));
+        assert!(module.to_string().contains("tail call i32 @test(i32 %0)"), "{}", module);
+    }
+
+    #[test]
+    fn variadic_calls_take_extra_arguments() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int32_type(&context)], int32_type(&context));
+        let variadic    = Function::new_variadic(&module, "variadic", &mut [int32_type(&context)], int32_type(&context));
+        let mut builder = Builder::new(&context);
+
+        builder.move_to_end(function.new_basic_block("entry"));
+
+        let param = function.param(0);
+
+        assert!(variadic.is_variadic());
+        assert!(!function.is_variadic());
+        assert_eq!(variadic.arity(), 1);
+        assert!(builder.call(variadic, &[], "call").is_err());
+
+        let call = builder.call(variadic, &[param, 1.0f64.to_representation(&context)], "call").unwrap();
+
+        builder.return_value(call);
+
+        assert_eq!(module.verify(), Ok(()));
+    }
+
+    #[test]
+    fn mismatched_operands_are_caught() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int32_type(&context)], int1_type(&context));
+        let mut builder = Builder::new(&context);
+
+        builder.move_to_end(function.new_basic_block("entry"));
+
+        // returns an `i32` from a function declared to return an `i1`
+        let sum = builder.add(function.param(0), function.param(0), "sum");
+
+        builder.return_value(sum);
+
+        assert!(module.verify().is_err());
+    }
+
+    // the names of a function's blocks, in the order they're laid out
+    fn block_names(function: Function) -> Vec<String> {
+        function.basic_blocks().map(|b| b.name()).collect()
+    }
+
+    #[test]
+    fn switches() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int32_type(&context)], int32_type(&context));
+        let mut builder = Builder::new(&context);
+
+        let entry   = function.new_basic_block("entry");
+        let one     = function.new_basic_block("one");
+        let two     = function.new_basic_block("two");
+        let default = function.new_basic_block("default");
+
+        builder.move_to_end(entry);
+        builder.switch(function.param(0), default, &[(1i32.to_representation(&context), one), (2i32.to_representation(&context), two)]);
+
+        for &(block, result) in &[(one, 10i32), (two, 20), (default, 0)] {
+            builder.move_to_end(block);
+            builder.return_value(result.to_representation(&context));
+        }
+
+        assert_eq!(module.verify(), Ok(()));
+        assert!(entry.terminator().is_some());
+
+        let ir = module.to_string();
+
+        assert!(ir.contains("switch i32 %0, label %default ["), "{}", ir);
+        assert!(ir.contains("i32 1, label %one"), "{}", ir);
+        assert!(ir.contains("i32 2, label %two"), "{}", ir);
+    }
+
+    #[test]
+    fn unreachable_ends_a_block() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int1_type(&context)], int32_type(&context));
+        let mut builder = Builder::new(&context);
+
+        let entry = function.new_basic_block("entry");
+        let done  = function.new_basic_block("done");
+        let never = function.new_basic_block("never");
+
+        builder.move_to_end(entry);
+        builder.cond_br(function.param(0), done, never);
+
+        builder.move_to_end(done);
+        builder.return_value(1i32.to_representation(&context));
+
+        builder.move_to_end(never);
+
+        assert!(never.terminator().is_none());
+
+        let unreachable = builder.unreachable();
+
+        assert_eq!(never.terminator().map(|t| t.to_ref()), Some(unreachable.to_ref()));
+        assert_eq!(module.verify(), Ok(()));
+        assert!(module.to_string().contains("  unreachable\n}"), "{}", module);
+    }
+
+    #[test]
+    fn blocks_are_named_ordered_moved_and_deleted() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [], int32_type(&context));
+        let mut builder = Builder::new(&context);
+
+        let entry  = function.new_basic_block("entry");
+        let middle = function.new_basic_block("middle");
+        let last   = function.new_basic_block("last");
+        let unused = function.new_basic_block("unused");
+
+        assert_eq!(middle.name(), "middle");
+        assert_eq!(middle.parent().name(), "test");
+        assert_eq!(entry.previous(), None);
+        assert_eq!(middle.previous(), Some(entry));
+        assert_eq!(middle.next(), Some(last));
+        assert_eq!(unused.next(), None);
+
+        last.move_before(middle);
+
+        assert_eq!(block_names(function), vec!["entry", "last", "middle", "unused"]);
+        assert_eq!(middle.previous(), Some(last));
+
+        last.move_after(unused);
+
+        assert_eq!(block_names(function), vec!["entry", "middle", "unused", "last"]);
+
+        // nothing branches to `unused`, so nothing is left pointing at it
+        unsafe {
+            unused.delete();
+        }
+
+        assert_eq!(block_names(function), vec!["entry", "middle", "last"]);
+        assert_eq!(last.previous(), Some(middle));
+
+        builder.move_to_end(entry);
+        builder.br(middle);
+        builder.move_to_end(middle);
+        builder.br(last);
+        builder.move_to_end(last);
+        builder.return_value(0i32.to_representation(&context));
+
+        assert_eq!(function.entry_block(), entry);
+        assert_eq!(module.verify(), Ok(()));
+    }
+
+    // the instructions of a block as the module prints them, one per line
+    fn instructions(module: &Module, block: &str) -> Vec<String> {
+        let ir    = module.to_string();
+        let start = ir.find(&format!("\n{}:", block)).unwrap() + 1;
+
+        ir[start ..].lines().skip(1).take_while(|l| !l.is_empty() && *l != "}").map(|l| l.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn stack_and_heap_memory() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int32_type(&context)], int32_type(&context));
+        let mut builder = Builder::new(&context);
+
+        builder.move_to_end(function.new_basic_block("entry"));
+
+        let count = function.param(0);
+        let slots = builder.array_alloca(int32_type(&context), count, "slots");
+        let one   = builder.malloc(int64_type(&context), "one");
+        let many  = builder.array_malloc(double_type(&context), count, "many");
+        let bytes = builder.bitcast(one, pointer_type(int8_type(&context), 0), "bytes");
+
+        assert_eq!(slots.ty(), pointer_type(int32_type(&context), 0));
+        assert_eq!(one.ty(), pointer_type(int64_type(&context), 0));
+        assert_eq!(many.ty(), pointer_type(double_type(&context), 0));
+
+        builder.store(count, slots);
+        builder.free(bytes);
+        builder.free(many);
+
+        let loaded = builder.load(slots, "loaded");
+
+        builder.return_value(loaded);
+
+        assert_eq!(module.verify(), Ok(()));
+
+        let ir = module.to_string();
+
+        assert!(ir.contains("%slots = alloca i32, i32 %0"), "{}", ir);
+        assert!(ir.contains("call i8* @malloc("), "{}", ir);
+        assert!(ir.contains("call void @free(i8* "), "{}", ir);
+        assert!(module.get_function("malloc").is_some());
+        assert!(module.get_function("free").is_some());
+    }
+
+    #[test]
+    fn entry_allocas_go_to_the_top_of_the_entry_block() {
+        let context     = Context::new();
+        let module      = Module::new("test", &context);
+        let function    = Function::new(&module, "test", &mut [int32_type(&context)], int32_type(&context));
+        let mut builder = Builder::new(&context);
+
+        let entry = function.new_basic_block("entry");
+        let body  = function.new_basic_block("body");
+
+        // an empty entry block takes the slot at its end
+        builder.move_to_end(entry);
+
+        let first = builder.entry_alloca(int32_type(&context), "first");
+
+        builder.store(function.param(0), first);
+        builder.br(body);
+
+        builder.move_to_end(body);
+
+        let loaded = builder.load(first, "loaded");
+        let second = builder.entry_alloca(int32_type(&context), "second");
+
+        // the builder is left where it was
+        assert_eq!(builder.insert_block(), body);
+
+        builder.store(loaded, second);
+
+        let result = builder.load(second, "result");
+
+        builder.return_value(result);
+
+        assert_eq!(module.verify(), Ok(()));
+        assert_eq!(instructions(&module, "entry"), vec![
+            "%first = alloca i32, align 4",
+            "%second = alloca i32, align 4",
+            "store i32 %0, i32* %first, align 4",
+            "br label %body",
+        ]);
+        assert_eq!(instructions(&module, "body")[0], "%loaded = load i32, i32* %first, align 4");
+    }
+}
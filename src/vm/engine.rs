@@ -0,0 +1,83 @@
+use vm::LLVMRef;
+use vm::module::Module;
+use vm::target::take_message;
+
+use vm::libc::c_char;
+use vm::llvm::execution_engine::{
+    LLVMCreateExecutionEngineForModule,
+    LLVMDisposeExecutionEngine,
+    LLVMExecutionEngineRef,
+    LLVMGetFunctionAddress,
+    LLVMLinkInMCJIT,
+};
+
+use vm::llvm::target::{
+    LLVM_InitializeNativeAsmPrinter,
+    LLVM_InitializeNativeTarget,
+};
+
+use std::ffi::CString;
+use std::mem;
+
+// compiles a module in memory to run its functions right away
+pub struct Engine {
+    engine: LLVMExecutionEngineRef,
+}
+
+impl Engine {
+    // the engine takes the module over, disposing of it along with itself
+    pub fn new(mut module: Module) -> Result<Engine, String> {
+        unsafe {
+            LLVMLinkInMCJIT();
+
+            if LLVM_InitializeNativeTarget() != 0 || LLVM_InitializeNativeAsmPrinter() != 0 {
+                return Err("Native target isn't available".to_string())
+            }
+
+            let mut engine = 0 as LLVMExecutionEngineRef;
+            let mut error  = 0 as *mut c_char;
+
+            if LLVMCreateExecutionEngineForModule(&mut engine, module.to_ref(), &mut error) != 0 {
+                return Err(take_message(error))
+            }
+
+            module.unown();
+
+            Ok(Engine {
+                engine: engine,
+            })
+        }
+    }
+
+    // calls a function taking nothing and returning an `i32`, like `main`
+    pub fn run(&self, name: &str) -> Result<i32, String> {
+        let c_name  = CString::new(name).unwrap();
+        let address = unsafe {
+            LLVMGetFunctionAddress(self.engine, c_name.as_ptr() as *const c_char)
+        };
+
+        if address == 0 {
+            return Err(format!("No function named `{}`", name))
+        }
+
+        let function: extern "C" fn() -> i32 = unsafe {
+            mem::transmute(address as usize)
+        };
+
+        Ok(function())
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposeExecutionEngine(self.engine);
+        }
+    }
+}
+
+impl LLVMRef<LLVMExecutionEngineRef> for Engine {
+    fn to_ref(&self) -> LLVMExecutionEngineRef {
+        self.engine
+    }
+}
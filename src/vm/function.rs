@@ -1,8 +1,10 @@
 use vm::LLVMRef;
 use vm::module::Module;
-use vm::builder::BasicBlock;
+use vm::builder::{BasicBlock, BasicBlocks};
+use vm::value::Value;
 
 use vm::libc::c_char;
+use vm::llvm::LLVMLinkage;
 use vm::llvm::analysis::{
     LLVMVerifierFailureAction,
     LLVMVerifyFunction,
@@ -12,8 +14,15 @@ use vm::llvm::core::{
     LLVMAddFunction,
     LLVMAppendBasicBlockInContext,
     LLVMCountParams,
+    LLVMGetElementType,
+    LLVMGetEntryBasicBlock,
+    LLVMGetFirstBasicBlock,
+    LLVMGetParam,
     LLVMFunctionType,
     LLVMGetTypeContext,
+    LLVMGetValueName,
+    LLVMIsFunctionVarArg,
+    LLVMSetLinkage,
     LLVMTypeOf,
 };
 
@@ -23,21 +32,37 @@ use vm::llvm::prelude::{
     LLVMValueRef,
 };
 
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 
+#[derive(Debug, Clone, Copy)]
 pub struct Function {
     func: LLVMValueRef,
 }
 
 impl Function {
+    pub fn from_ref(func_ref: LLVMValueRef) -> Function {
+        Function {
+            func: func_ref,
+        }
+    }
+
     pub fn new(module: &Module, name: &str, args: &mut [LLVMTypeRef], ret: LLVMTypeRef) -> Function {
+        Self::add(module, name, args, ret, false)
+    }
+
+    // takes any number of arguments after `args`, like C's `printf`
+    pub fn new_variadic(module: &Module, name: &str, args: &mut [LLVMTypeRef], ret: LLVMTypeRef) -> Function {
+        Self::add(module, name, args, ret, true)
+    }
+
+    fn add(module: &Module, name: &str, args: &mut [LLVMTypeRef], ret: LLVMTypeRef, variadic: bool) -> Function {
         let name = CString::new(name).unwrap();
         let func_type = unsafe {
             LLVMFunctionType(
                 ret,
                 args.as_mut_ptr(),
                 args.len() as u32,
-                0 as LLVMBool,
+                variadic as LLVMBool,
             )
         };
 
@@ -52,6 +77,19 @@ impl Function {
         }
     }
 
+    // only visible inside its module, which keeps it out of the symbols of object files
+    pub fn set_internal(&self) {
+        unsafe {
+            LLVMSetLinkage(self.to_ref(), LLVMLinkage::LLVMInternalLinkage);
+        }
+    }
+
+    pub fn name(&self) -> String {
+        unsafe {
+            CStr::from_ptr(LLVMGetValueName(self.to_ref())).to_string_lossy().into_owned()
+        }
+    }
+
     pub fn new_basic_block(&self, name: &str) -> BasicBlock {
         let name = CString::new(name).unwrap();
 
@@ -66,12 +104,38 @@ impl Function {
         })
     }
 
+    // the block the function starts in, the first one added
+    pub fn entry_block(&self) -> BasicBlock {
+        BasicBlock::from_ref(unsafe {
+            LLVMGetEntryBasicBlock(self.to_ref())
+        })
+    }
+
+    pub fn basic_blocks(&self) -> BasicBlocks {
+        BasicBlocks::new(unsafe {
+            LLVMGetFirstBasicBlock(self.to_ref())
+        })
+    }
+
     pub fn arity(&self) -> u32 {
         unsafe {
             LLVMCountParams(self.to_ref()) as u32
         }
     }
 
+    // whether it takes more arguments than it has parameters, see `new_variadic`
+    pub fn is_variadic(&self) -> bool {
+        unsafe {
+            LLVMIsFunctionVarArg(LLVMGetElementType(LLVMTypeOf(self.to_ref()))) != 0
+        }
+    }
+
+    pub fn param(&self, index: u32) -> Value {
+        Value::from_ref(unsafe {
+            LLVMGetParam(self.to_ref(), index)
+        })
+    }
+
     pub fn verify(&self) -> Result<(), String> {
         let status = unsafe {
             LLVMVerifyFunction(
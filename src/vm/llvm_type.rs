@@ -19,6 +19,7 @@ use vm::llvm::prelude::{
     LLVMTypeRef,
 };
 
+use std::ffi::CStr;
 use std::mem;
 
 macro_rules! bind_llvm_type {
@@ -80,6 +81,80 @@ pub fn vector_type(element_type: LLVMTypeRef, size: u32) -> LLVMTypeRef {
     }
 }
 
+pub fn struct_type(context: &Context, fields: &mut [LLVMTypeRef], packed: bool) -> LLVMTypeRef {
+    use vm::llvm::core::LLVMStructTypeInContext;
+
+    unsafe {
+        LLVMStructTypeInContext(context.to_ref(), fields.as_mut_ptr(), fields.len() as c_uint, packed as LLVMBool)
+    }
+}
+
+pub fn function_type(ret: LLVMTypeRef, params: &mut [LLVMTypeRef]) -> LLVMTypeRef {
+    use vm::llvm::core::LLVMFunctionType;
+
+    unsafe {
+        LLVMFunctionType(ret, params.as_mut_ptr(), params.len() as c_uint, 0 as LLVMBool)
+    }
+}
+
+// the null pointer of a pointer type
+pub fn null(ty: LLVMTypeRef) -> Value {
+    use vm::llvm::core::LLVMConstPointerNull;
+
+    Value::from_ref(unsafe {
+        LLVMConstPointerNull(ty)
+    })
+}
+
+// a value of the type that's yet to be filled in, e.g. a struct built up with `insertvalue`
+pub fn undef(ty: LLVMTypeRef) -> Value {
+    use vm::llvm::core::LLVMGetUndef;
+
+    Value::from_ref(unsafe {
+        LLVMGetUndef(ty)
+    })
+}
+
+// how many bytes a value of the type takes up, as an `i64` constant
+pub fn size_of(ty: LLVMTypeRef) -> Value {
+    use vm::llvm::core::LLVMSizeOf;
+
+    Value::from_ref(unsafe {
+        LLVMSizeOf(ty)
+    })
+}
+
+// how a type is written in LLVM IR, e.g. `i32*`
+pub fn type_name(ty: LLVMTypeRef) -> String {
+    use vm::llvm::core::{LLVMDisposeMessage, LLVMPrintTypeToString};
+
+    unsafe {
+        let name_string = LLVMPrintTypeToString(ty);
+        let name        = CStr::from_ptr(name_string).to_string_lossy().into_owned();
+
+        LLVMDisposeMessage(name_string);
+        name
+    }
+}
+
+// the environment of a closure, holding its captured variables
+pub fn environment_type(context: &Context, captures: &mut [LLVMTypeRef]) -> LLVMTypeRef {
+    struct_type(context, captures, false)
+}
+
+// closures are passed around as `{ ret (i8*, params...)*, i8* }`: a pointer to the lambda's code, taking
+// its environment as a leading argument, and a pointer to the environment itself
+pub fn closure_type(context: &Context, params: &[LLVMTypeRef], ret: LLVMTypeRef) -> LLVMTypeRef {
+    let environment = pointer_type(int8_type(context), 0);
+
+    let mut code_params = vec![environment];
+    code_params.extend_from_slice(params);
+
+    let code = pointer_type(function_type(ret, &mut code_params), 0);
+
+    struct_type(context, &mut [code, environment], false)
+}
+
 pub trait VMRepresentation {
     fn to_representation(self, context: &Context) -> Value;
 }
@@ -7,6 +7,11 @@ pub mod value;
 pub mod llvm_type;
 pub mod builder;
 pub mod function;
+pub mod target;
+pub mod engine;
+
+// types are passed around as LLVM's own references
+pub use self::llvm::prelude::LLVMTypeRef;
 
 pub trait LLVMRef<R> {
     fn to_ref(&self) -> R;
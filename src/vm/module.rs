@@ -1,5 +1,6 @@
 use super::LLVMRef;
 use super::context::Context;
+use super::function::Function;
 
 use vm::libc::c_char;
 use vm::llvm::analysis::{
@@ -10,11 +11,17 @@ use vm::llvm::analysis::{
 use vm::llvm::core::{
     LLVMDisposeMessage,
     LLVMDisposeModule,
+    LLVMGetFirstFunction,
+    LLVMGetNamedFunction,
+    LLVMGetNextFunction,
     LLVMModuleCreateWithNameInContext,
     LLVMPrintModuleToString,
 };
 
-use vm::llvm::prelude::LLVMModuleRef;
+use vm::llvm::prelude::{
+    LLVMModuleRef,
+    LLVMValueRef,
+};
 
 use std::ffi::{CStr, CString};
 use std::fmt;
@@ -43,6 +50,27 @@ impl Module {
         self.owned = false;
     }
 
+    // a function added to the module, whether it's been defined or only declared
+    pub fn get_function(&self, name: &str) -> Option<Function> {
+        let name     = CString::new(name).unwrap();
+        let function = unsafe {
+            LLVMGetNamedFunction(self.to_ref(), name.as_ptr() as *const c_char)
+        };
+
+        if function.is_null() {
+            None
+        } else {
+            Some(Function::from_ref(function))
+        }
+    }
+
+    // every function in the module, in the order they were added
+    pub fn functions(&self) -> Functions {
+        Functions {
+            next: unsafe { LLVMGetFirstFunction(self.to_ref()) },
+        }
+    }
+
     pub fn verify(&self) -> Result<(), String> {
         let mut verify_err = 0 as *mut c_char;
         let status = unsafe {
@@ -93,10 +121,65 @@ impl fmt::Display for Module {
         write!(f, "{}", unsafe {
             let ir_string = LLVMPrintModuleToString(self.to_ref());
             let ir        = CStr::from_ptr(ir_string).to_string_lossy()
-                                            .to_owned();
+                                            .into_owned();
             
             LLVMDisposeMessage(ir_string);
             ir
         })
     }
-}
\ No newline at end of file
+}
+
+pub struct Functions {
+    next: LLVMValueRef,
+}
+
+impl Iterator for Functions {
+    type Item = Function;
+
+    fn next(&mut self) -> Option<Function> {
+        if self.next.is_null() {
+            return None
+        }
+
+        let function = self.next;
+
+        self.next = unsafe { LLVMGetNextFunction(function) };
+
+        Some(Function::from_ref(function))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use vm::llvm_type::{int32_type, void_type};
+
+    #[test]
+    fn functions_are_found_by_name() {
+        let context = Context::new();
+        let module  = Module::new("test", &context);
+        let added   = Function::new(&module, "add", &mut [int32_type(&context), int32_type(&context)], int32_type(&context));
+
+        let found = module.get_function("add").unwrap();
+
+        assert_eq!(found.to_ref(), added.to_ref());
+        assert_eq!(found.arity(), 2);
+        assert!(module.get_function("sub").is_none());
+        assert!(module.get_function("").is_none());
+    }
+
+    #[test]
+    fn functions_are_listed_in_order() {
+        let context = Context::new();
+        let module  = Module::new("test", &context);
+
+        assert_eq!(module.functions().count(), 0);
+
+        for name in &["first", "second", "third"] {
+            Function::new(&module, name, &mut [], void_type(&context));
+        }
+
+        assert_eq!(module.functions().map(|f| f.name()).collect::<Vec<_>>(), vec!["first", "second", "third"]);
+    }
+}
@@ -0,0 +1,130 @@
+use vm::LLVMRef;
+use vm::module::Module;
+
+use vm::libc::c_char;
+use vm::llvm::core::{
+    LLVMDisposeMemoryBuffer,
+    LLVMDisposeMessage,
+    LLVMGetBufferSize,
+    LLVMGetBufferStart,
+};
+
+use vm::llvm::target::{
+    LLVM_InitializeNativeAsmPrinter,
+    LLVM_InitializeNativeTarget,
+};
+
+use vm::llvm::target_machine::{
+    LLVMCodeGenFileType,
+    LLVMCodeGenOptLevel,
+    LLVMCodeModel,
+    LLVMCreateTargetMachine,
+    LLVMDisposeTargetMachine,
+    LLVMGetDefaultTargetTriple,
+    LLVMGetTargetFromTriple,
+    LLVMRelocMode,
+    LLVMTargetMachineEmitToMemoryBuffer,
+    LLVMTargetMachineRef,
+    LLVMTargetRef,
+};
+
+use std::ffi::CStr;
+use std::ptr;
+use std::slice;
+
+pub enum FileType {
+    Assembly,
+    Object,
+}
+
+// the machine code is generated for
+pub struct TargetMachine {
+    machine: LLVMTargetMachineRef,
+}
+
+impl TargetMachine {
+    // the machine this is running on
+    pub fn native() -> Result<TargetMachine, String> {
+        unsafe {
+            if LLVM_InitializeNativeTarget() != 0 || LLVM_InitializeNativeAsmPrinter() != 0 {
+                return Err("Native target isn't available".to_string())
+            }
+
+            let triple     = LLVMGetDefaultTargetTriple();
+            let mut target = 0 as LLVMTargetRef;
+            let mut error  = 0 as *mut c_char;
+
+            if LLVMGetTargetFromTriple(triple, &mut target, &mut error) != 0 {
+                LLVMDisposeMessage(triple);
+
+                return Err(take_message(error))
+            }
+
+            let machine = LLVMCreateTargetMachine(
+                target,
+                triple,
+                b"generic\0".as_ptr() as *const c_char,
+                b"\0".as_ptr() as *const c_char,
+                LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+                LLVMRelocMode::LLVMRelocPIC,
+                LLVMCodeModel::LLVMCodeModelDefault,
+            );
+
+            LLVMDisposeMessage(triple);
+
+            Ok(TargetMachine {
+                machine: machine,
+            })
+        }
+    }
+
+    pub fn emit(&self, module: &Module, file_type: FileType) -> Result<Vec<u8>, String> {
+        let file_type = match file_type {
+            FileType::Assembly => LLVMCodeGenFileType::LLVMAssemblyFile,
+            FileType::Object   => LLVMCodeGenFileType::LLVMObjectFile,
+        };
+
+        unsafe {
+            let mut error  = 0 as *mut c_char;
+            let mut buffer = ptr::null_mut();
+
+            if LLVMTargetMachineEmitToMemoryBuffer(self.machine, module.to_ref(), file_type, &mut error, &mut buffer) != 0 {
+                return Err(take_message(error))
+            }
+
+            let start = LLVMGetBufferStart(buffer) as *const u8;
+            let bytes = slice::from_raw_parts(start, LLVMGetBufferSize(buffer) as usize).to_vec();
+
+            LLVMDisposeMemoryBuffer(buffer);
+
+            Ok(bytes)
+        }
+    }
+}
+
+impl Drop for TargetMachine {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposeTargetMachine(self.machine);
+        }
+    }
+}
+
+impl LLVMRef<LLVMTargetMachineRef> for TargetMachine {
+    fn to_ref(&self) -> LLVMTargetMachineRef {
+        self.machine
+    }
+}
+
+// copies out a message LLVM allocated, then frees it
+pub unsafe fn take_message(message: *mut c_char) -> String {
+    if message.is_null() {
+        return "Unknown error".to_string()
+    }
+
+    let text = CStr::from_ptr(message).to_string_lossy().into_owned();
+
+    LLVMDisposeMessage(message);
+
+    text
+}
@@ -5,13 +5,18 @@ use vm::LLVMRef;
 use vm::llvm::core::{
     LLVMDisposeMessage,
     LLVMPrintValueToString,    
+    LLVMTypeOf,
 };
 
-use vm::llvm::prelude::LLVMValueRef;
+use vm::llvm::prelude::{
+    LLVMTypeRef,
+    LLVMValueRef,
+};
 
 use std::ffi::CStr;
 use std::fmt;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Value {
     value: LLVMValueRef
 }
@@ -22,6 +27,12 @@ impl Value {
             value: value,
         }
     }
+
+    pub fn ty(&self) -> LLVMTypeRef {
+        unsafe {
+            LLVMTypeOf(self.to_ref())
+        }
+    }
 }
 
 impl LLVMRef<LLVMValueRef> for Value {